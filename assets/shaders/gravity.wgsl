@@ -0,0 +1,54 @@
+// All-pairs gravitational acceleration, tiled through workgroup memory.
+
+struct Params {
+    count: u32,
+    gravity: f32,
+    min_distance: f32,
+    _padding: f32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+// xy: position, z: mass
+@group(0) @binding(1) var<storage, read> bodies: array<vec4<f32>>;
+@group(0) @binding(2) var<storage, read_write> accelerations: array<vec2<f32>>;
+
+const TILE_SIZE: u32 = 64u;
+var<workgroup> tile: array<vec4<f32>, TILE_SIZE>;
+
+@compute @workgroup_size(64)
+fn main(
+    @builtin(global_invocation_id) global_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+) {
+    let i = global_id.x;
+    var pos = vec2<f32>(0.0);
+    if i < params.count {
+        pos = bodies[i].xy;
+    }
+
+    var acc = vec2<f32>(0.0);
+    for (var base = 0u; base < params.count; base += TILE_SIZE) {
+        let j = base + local_id.x;
+        if j < params.count {
+            tile[local_id.x] = bodies[j];
+        } else {
+            // Massless padding contributes nothing
+            tile[local_id.x] = vec4<f32>(0.0);
+        }
+        workgroupBarrier();
+
+        for (var k = 0u; k < TILE_SIZE; k++) {
+            let other = tile[k];
+            let direction = other.xy - pos;
+            let distance = max(length(direction), params.min_distance);
+            if base + k != i {
+                acc += direction * (params.gravity * other.z / (distance * distance * distance));
+            }
+        }
+        workgroupBarrier();
+    }
+
+    if i < params.count {
+        accelerations[i] = acc;
+    }
+}
@@ -1,4 +1,11 @@
+use bevy::ecs::system::EntityCommands;
 use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+
+/// Side length of the generated circle texture in pixels.
+const CIRCLE_TEXTURE_SIZE: u32 = 128;
 
 #[derive(Component, Debug, Clone, Copy, Reflect)]
 #[reflect(Component)]
@@ -11,6 +18,9 @@ pub struct Body {
     pub y: f32,
     pub a_x: f32,
     pub a_y: f32,
+    /// Net acceleration of the last step, kept after `a_x`/`a_y` are reset.
+    pub last_a_x: f32,
+    pub last_a_y: f32,
     pub v_x: f32,
     pub v_y: f32,
     pub mass: f32,
@@ -33,10 +43,79 @@ impl Body {
             v_y,
             a_x: 0f32,
             a_y: 0f32,
+            last_a_x: 0f32,
+            last_a_y: 0f32,
             mass: (4.0 / 3.0) * PI * size.powi(3) * density,
             size,
             density,
             color: Color::rgb(1.0, 1.0, 1.0),
         }
     }
+
+    /// Volume of the body as a sphere, which its mass is derived from.
+    pub fn volume(&self) -> f32 {
+        (4.0 / 3.0) * std::f32::consts::PI * self.size.powi(3)
+    }
+}
+
+/// Rendering assets shared by every body. All bodies are sprites with the
+/// same circle texture, so they are drawn in a few batched draw calls with
+/// the color passed per instance, no matter how many there are.
+#[derive(Resource)]
+pub struct BodyAssets {
+    pub circle: Handle<Image>,
+}
+
+impl FromWorld for BodyAssets {
+    fn from_world(world: &mut World) -> Self {
+        let size = CIRCLE_TEXTURE_SIZE;
+        let radius = size as f32 / 2.0;
+        let mut data = Vec::with_capacity((size * size * 4) as usize);
+        for y in 0..size {
+            for x in 0..size {
+                let offset = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - Vec2::splat(radius);
+                // One pixel of antialiasing at the edge
+                let alpha = (radius - offset.length()).clamp(0.0, 1.0);
+                data.extend_from_slice(&[255, 255, 255, (alpha * 255.0) as u8]);
+            }
+        }
+        let mut image = Image::new(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::RENDER_WORLD,
+        );
+        image.sampler = ImageSampler::linear();
+
+        BodyAssets {
+            circle: world.resource_mut::<Assets<Image>>().add(image),
+        }
+    }
+}
+
+/// Spawns `body` with a sprite sized and placed to match it.
+pub fn spawn_body<'a>(
+    commands: &'a mut Commands,
+    assets: &BodyAssets,
+    body: Body,
+) -> EntityCommands<'a> {
+    commands.spawn((
+        body,
+        SpriteBundle {
+            sprite: Sprite {
+                color: body.color,
+                // The texture is a unit-radius circle, scaled by the body size
+                custom_size: Some(Vec2::splat(2.0)),
+                ..default()
+            },
+            texture: assets.circle.clone(),
+            transform: Transform::from_xyz(body.x, body.y, 0.0).with_scale(Vec3::splat(body.size)),
+            ..default()
+        },
+    ))
 }
@@ -0,0 +1,269 @@
+use bevy::prelude::*;
+
+use crate::GRAVITY_CONST;
+use crate::body::Body;
+use crate::colormap::Colormap;
+
+const LEGEND_SWATCHES: usize = 20;
+const BOUND_COLOR: Color = Color::rgb(0.3, 0.6, 1.0);
+const UNBOUND_COLOR: Color = Color::rgb(1.0, 0.35, 0.2);
+
+/// Quantity bodies are colored by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// The body's own `Body::color`.
+    #[default]
+    Body,
+    Speed,
+    Mass,
+    Density,
+    KineticEnergy,
+    Acceleration,
+    /// Whether the body is gravitationally bound to the rest of the system.
+    Bound,
+}
+
+impl ColorMode {
+    pub fn next(self) -> Self {
+        match self {
+            ColorMode::Body => ColorMode::Speed,
+            ColorMode::Speed => ColorMode::Mass,
+            ColorMode::Mass => ColorMode::Density,
+            ColorMode::Density => ColorMode::KineticEnergy,
+            ColorMode::KineticEnergy => ColorMode::Acceleration,
+            ColorMode::Acceleration => ColorMode::Bound,
+            ColorMode::Bound => ColorMode::Body,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ColorMode::Body => "BODY",
+            ColorMode::Speed => "SPEED",
+            ColorMode::Mass => "MASS",
+            ColorMode::Density => "DENSITY",
+            ColorMode::KineticEnergy => "KINETIC ENERGY",
+            ColorMode::Acceleration => "ACCELERATION",
+            ColorMode::Bound => "BOUND",
+        }
+    }
+
+    /// Quantities spanning many orders of magnitude are mapped on a log scale.
+    fn logarithmic(self) -> bool {
+        matches!(
+            self,
+            ColorMode::Mass | ColorMode::KineticEnergy | ColorMode::Acceleration
+        )
+    }
+
+    fn value(self, body: &Body) -> Option<f32> {
+        let value = match self {
+            ColorMode::Body | ColorMode::Bound => return None,
+            ColorMode::Speed => Vec2::new(body.v_x, body.v_y).length(),
+            ColorMode::Mass => body.mass,
+            ColorMode::Density => body.density,
+            ColorMode::KineticEnergy => {
+                0.5 * body.mass * Vec2::new(body.v_x, body.v_y).length_squared()
+            }
+            ColorMode::Acceleration => Vec2::new(body.last_a_x, body.last_a_y).length(),
+        };
+        Some(if self.logarithmic() {
+            value.max(f32::MIN_POSITIVE).log10()
+        } else {
+            value
+        })
+    }
+}
+
+/// Color mapping for bodies. The value range and the system totals used for
+/// the bound test are refreshed every frame by `body_color_range_system`.
+#[derive(Resource, Default)]
+pub struct BodyColoring {
+    pub mode: ColorMode,
+    pub colormap: Colormap,
+    /// Range of the mapped value, log10 for logarithmic modes.
+    range: Option<(f32, f32)>,
+    total_mass: f32,
+    /// Mass-weighted position and velocity sums of all bodies.
+    weighted_pos: Vec2,
+    weighted_vel: Vec2,
+}
+
+impl BodyColoring {
+    /// The color `body` is drawn with under the current mode. Mapped colors
+    /// keep the body's own alpha so gas stays translucent.
+    pub fn color(&self, body: &Body) -> Color {
+        match self.mode {
+            ColorMode::Body => body.color,
+            ColorMode::Bound if self.is_bound(body) => BOUND_COLOR.with_a(body.color.a()),
+            ColorMode::Bound => UNBOUND_COLOR.with_a(body.color.a()),
+            mode => match (mode.value(body), self.range) {
+                (Some(value), Some((lo, hi))) => self
+                    .colormap
+                    .sample((value - lo) / (hi - lo).max(f32::EPSILON))
+                    .with_a(body.color.a()),
+                _ => body.color,
+            },
+        }
+    }
+
+    /// Treats everything else as a point mass at its center of mass and
+    /// checks whether the two-body orbital energy is negative.
+    fn is_bound(&self, body: &Body) -> bool {
+        let mass = self.total_mass - body.mass;
+        if mass <= 0.0 {
+            return false;
+        }
+        let pos = Vec2::new(body.x, body.y);
+        let vel = Vec2::new(body.v_x, body.v_y);
+        let rest_pos = (self.weighted_pos - pos * body.mass) / mass;
+        let rest_vel = (self.weighted_vel - vel * body.mass) / mass;
+        let distance = pos.distance(rest_pos).max(body.size);
+        let mu = GRAVITY_CONST * (mass + body.mass);
+        0.5 * vel.distance_squared(rest_vel) - mu / distance < 0.0
+    }
+}
+
+pub fn body_color_range_system(mut coloring: ResMut<BodyColoring>, bodies: Query<&Body>) {
+    let mode = coloring.mode;
+    coloring.range = bodies
+        .iter()
+        .filter_map(|body| mode.value(body))
+        .fold(None, |range, v| match range {
+            None => Some((v, v)),
+            Some((lo, hi)) => Some((v.min(lo), v.max(hi))),
+        });
+
+    let (mut total_mass, mut weighted_pos, mut weighted_vel) = (0.0, Vec2::ZERO, Vec2::ZERO);
+    for body in bodies.iter() {
+        total_mass += body.mass;
+        weighted_pos += Vec2::new(body.x, body.y) * body.mass;
+        weighted_vel += Vec2::new(body.v_x, body.v_y) * body.mass;
+    }
+    coloring.total_mass = total_mass;
+    coloring.weighted_pos = weighted_pos;
+    coloring.weighted_vel = weighted_vel;
+}
+
+pub fn body_color_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut coloring: ResMut<BodyColoring>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyB) {
+        if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            coloring.colormap = coloring.colormap.next();
+        } else {
+            coloring.mode = coloring.mode.next();
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ColorLegend;
+
+#[derive(Component)]
+pub struct ColorLegendText;
+
+#[derive(Component)]
+pub struct ColorLegendSwatch(usize);
+
+pub fn color_legend_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/start.ttf");
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            ColorLegend,
+        ))
+        .with_children(|legend| {
+            legend.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font,
+                        font_size: 10.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                ColorLegendText,
+            ));
+            legend
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|bar| {
+                    for i in 0..LEGEND_SWATCHES {
+                        bar.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Px(12.0),
+                                    height: Val::Px(10.0),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            ColorLegendSwatch(i),
+                        ));
+                    }
+                });
+        });
+}
+
+/// Shows the colormap and the value range it spans for the current mode.
+pub fn color_legend_update_system(
+    coloring: Res<BodyColoring>,
+    mut legend: Query<&mut Visibility, With<ColorLegend>>,
+    mut text: Query<&mut Text, With<ColorLegendText>>,
+    mut swatches: Query<(&ColorLegendSwatch, &mut BackgroundColor)>,
+) {
+    let mode = coloring.mode;
+    for mut visibility in legend.iter_mut() {
+        *visibility = if mode == ColorMode::Body {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+    if mode == ColorMode::Body {
+        return;
+    }
+
+    let label = match (mode, coloring.range) {
+        (ColorMode::Bound, _) => "BOUND / UNBOUND".to_string(),
+        (_, None) => format!("{}: -", mode.label()),
+        (_, Some((lo, hi))) if mode.logarithmic() => format!(
+            "{}: {:.2E} - {:.2E} (LOG)",
+            mode.label(),
+            10f32.powf(lo),
+            10f32.powf(hi)
+        ),
+        (_, Some((lo, hi))) => format!("{}: {:.2} - {:.2}", mode.label(), lo, hi),
+    };
+    for mut text in text.iter_mut() {
+        text.sections[0].value = label.clone();
+    }
+
+    for (swatch, mut background) in swatches.iter_mut() {
+        let t = swatch.0 as f32 / (LEGEND_SWATCHES - 1) as f32;
+        background.0 = match mode {
+            ColorMode::Bound if t < 0.5 => BOUND_COLOR,
+            ColorMode::Bound => UNBOUND_COLOR,
+            _ => coloring.colormap.sample(t),
+        };
+    }
+}
@@ -0,0 +1,258 @@
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+
+use crate::body::Body;
+use crate::selection::Selection;
+use crate::view_frame::ViewFrame;
+
+const MIN_SCALE: f32 = 0.01;
+const MAX_SCALE: f32 = 100.0;
+/// Zoom factor per wheel notch.
+const ZOOM_STEP: f32 = 0.1;
+/// Pixel-precise wheels (touchpads) report this many pixels per notch.
+const PIXELS_PER_LINE: f32 = 40.0;
+/// Keyboard pan speed in screen pixels per second.
+const PAN_SPEED: f32 = 500.0;
+/// How quickly the camera catches up with its target, per second.
+const SMOOTHING: f32 = 15.0;
+/// How quickly pan momentum dies out after a drag, per second.
+const INERTIA_DAMPING: f32 = 4.0;
+/// Bodies further than this many median distances from the median position
+/// are treated as escapers and left out when fitting.
+const FIT_OUTLIER_FACTOR: f32 = 5.0;
+/// Fraction of the screen left empty around fitted bodies.
+const FIT_MARGIN: f32 = 0.1;
+
+/// What the camera keeps centered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraFollow {
+    #[default]
+    Free,
+    Body(Entity),
+    Barycenter,
+}
+
+/// Where the camera is heading. Input moves the target and the camera eases
+/// towards it, which gives smooth zoom and pan with momentum after drags.
+#[derive(Resource)]
+pub struct CameraController {
+    pub target_translation: Vec2,
+    pub target_scale: f32,
+    /// Panning by hand returns the camera to `CameraFollow::Free`.
+    pub follow: CameraFollow,
+    /// Pan momentum in world units per second.
+    velocity: Vec2,
+    /// Cursor position on the previous frame of a middle/right drag.
+    drag_cursor: Option<Vec2>,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController {
+            target_translation: Vec2::ZERO,
+            target_scale: 1.0,
+            follow: CameraFollow::Free,
+            velocity: Vec2::ZERO,
+            drag_cursor: None,
+        }
+    }
+}
+
+impl CameraController {
+    /// Eases back to the origin at the default zoom.
+    pub fn reset(&mut self) {
+        *self = CameraController::default();
+    }
+
+    /// Zooms by `steps` wheel notches keeping the point under `anchor` fixed.
+    /// `anchor` is an offset from the screen center in pixels, y up.
+    fn zoom(&mut self, steps: f32, anchor: Vec2) {
+        let scale =
+            (self.target_scale * (1.0 + ZOOM_STEP).powf(-steps)).clamp(MIN_SCALE, MAX_SCALE);
+        let anchor_world = self.target_translation + anchor * self.target_scale;
+        self.target_translation = anchor_world - anchor * scale;
+        self.target_scale = scale;
+    }
+}
+
+pub fn camera_control_system(
+    mut camera_query: Query<&mut Transform, With<Camera2d>>,
+    windows: Query<&Window>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mut controller: ResMut<CameraController>,
+    time: Res<Time>,
+) {
+    let mut camera_transform = camera_query.single_mut();
+    let window = windows.single();
+    let dt = time.delta_seconds();
+    // Cursor as an offset from the screen center, y up like the world
+    let cursor = window.cursor_position().map(|cursor| {
+        Vec2::new(
+            cursor.x - window.width() / 2.0,
+            window.height() / 2.0 - cursor.y,
+        )
+    });
+
+    // Keyboard pan; Ctrl combinations like Ctrl+D are editing commands
+    let mut direction = Vec2::ZERO;
+    if !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        if keyboard_input.pressed(KeyCode::KeyW) {
+            direction.y += 1.0;
+        }
+        if keyboard_input.pressed(KeyCode::KeyS) {
+            direction.y -= 1.0;
+        }
+        if keyboard_input.pressed(KeyCode::KeyA) {
+            direction.x -= 1.0;
+        }
+        if keyboard_input.pressed(KeyCode::KeyD) {
+            direction.x += 1.0;
+        }
+    }
+    if direction != Vec2::ZERO {
+        controller.follow = CameraFollow::Free;
+        controller.velocity = direction.normalize() * PAN_SPEED * controller.target_scale;
+    }
+
+    // Middle or right drag pans, leaving momentum behind on release
+    let dragging = mouse_button_input.any_pressed([MouseButton::Middle, MouseButton::Right]);
+    match (dragging, cursor) {
+        (true, Some(cursor)) => {
+            controller.follow = CameraFollow::Free;
+            if let Some(last) = controller.drag_cursor {
+                let delta = (last - cursor) * controller.target_scale;
+                controller.target_translation += delta;
+                if dt > 0.0 {
+                    controller.velocity = delta / dt;
+                }
+            }
+            controller.drag_cursor = Some(cursor);
+        }
+        _ => {
+            controller.drag_cursor = None;
+            let velocity = controller.velocity;
+            controller.target_translation += velocity * dt;
+            if direction == Vec2::ZERO {
+                controller.velocity *= (-INERTIA_DAMPING * dt).exp();
+            }
+        }
+    }
+
+    // Mouse wheel zoom, anchored at the cursor
+    for event in mouse_wheel_events.read() {
+        let steps = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / PIXELS_PER_LINE,
+        };
+        controller.zoom(steps, cursor.unwrap_or(Vec2::ZERO));
+    }
+
+    // Ease towards the target; zoom is interpolated in log space so it feels
+    // the same speed zooming in and out. Drags follow the cursor exactly.
+    let t = if controller.drag_cursor.is_some() {
+        1.0
+    } else {
+        1.0 - (-SMOOTHING * dt).exp()
+    };
+    let translation = camera_transform
+        .translation
+        .truncate()
+        .lerp(controller.target_translation, t);
+    let log_scale = camera_transform.scale.x.ln();
+    let scale = (log_scale + (controller.target_scale.ln() - log_scale) * t).exp();
+    camera_transform.translation = translation.extend(camera_transform.translation.z);
+    camera_transform.scale = Vec3::splat(scale);
+}
+
+/// Moves the camera target onto the followed body or barycenter, and
+/// handles the lock-on and fit keys.
+pub fn camera_follow_system(
+    mut controller: ResMut<CameraController>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    selection: Res<Selection>,
+    frame: Res<ViewFrame>,
+    bodies: Query<(Entity, &Body)>,
+    windows: Query<&Window>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyL) {
+        let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        let follow = match (shift, selection.primary()) {
+            (true, _) => CameraFollow::Barycenter,
+            (false, Some(selected)) => CameraFollow::Body(selected),
+            (false, None) => CameraFollow::Free,
+        };
+        // Pressing the same lock again releases it
+        controller.follow = if controller.follow == follow {
+            CameraFollow::Free
+        } else {
+            follow
+        };
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyF) {
+        let window = windows.single();
+        let points = bodies
+            .iter()
+            .map(|(_, body)| {
+                (
+                    frame.transform.to_view(Vec2::new(body.x, body.y)),
+                    body.size,
+                )
+            })
+            .collect::<Vec<_>>();
+        if let Some((center, size)) = fit_bounds(&points) {
+            let scale = (size / Vec2::new(window.width(), window.height())).max_element();
+            controller.target_translation = center;
+            controller.target_scale =
+                (scale / (1.0 - 2.0 * FIT_MARGIN)).clamp(MIN_SCALE, MAX_SCALE);
+            controller.follow = CameraFollow::Free;
+        }
+    }
+
+    let target = match controller.follow {
+        CameraFollow::Free => return,
+        CameraFollow::Body(entity) => bodies
+            .get(entity)
+            .ok()
+            .map(|(_, body)| Vec2::new(body.x, body.y)),
+        CameraFollow::Barycenter => {
+            let (weighted, total_mass) =
+                bodies.iter().fold((Vec2::ZERO, 0.0), |(sum, m), (_, b)| {
+                    (sum + Vec2::new(b.x, b.y) * b.mass, m + b.mass)
+                });
+            (total_mass > 0.0).then(|| weighted / total_mass)
+        }
+    };
+    match target {
+        Some(target) => controller.target_translation = frame.transform.to_view(target),
+        // The followed body is gone
+        None => controller.follow = CameraFollow::Free,
+    }
+}
+
+/// Center and size of the box around `(position, radius)` points, leaving
+/// out escapers far from the rest.
+fn fit_bounds(points: &[(Vec2, f32)]) -> Option<(Vec2, Vec2)> {
+    let median = |mut values: Vec<f32>| {
+        let mid = values.len() / 2;
+        *values.select_nth_unstable_by(mid, f32::total_cmp).1
+    };
+    if points.is_empty() {
+        return None;
+    }
+    let center = Vec2::new(
+        median(points.iter().map(|(p, _)| p.x).collect()),
+        median(points.iter().map(|(p, _)| p.y).collect()),
+    );
+    let typical = median(points.iter().map(|(p, _)| p.distance(center)).collect());
+
+    let (min, max) = points
+        .iter()
+        .filter(|(p, _)| typical == 0.0 || p.distance(center) <= typical * FIT_OUTLIER_FACTOR)
+        .fold((Vec2::MAX, Vec2::MIN), |(min, max), (p, radius)| {
+            (min.min(*p - *radius), max.max(*p + *radius))
+        });
+    Some(((min + max) / 2.0, max - min))
+}
@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+
+use crate::body::{Body, BodyAssets, spawn_body};
+use crate::diagnostics::ConservationDiagnostics;
+use crate::history::HistoryRecorder;
+use crate::selection::{CenterOfMass, Selection};
+use crate::sph::GasParticle;
+use crate::view_frame::ViewFrame;
+
+/// Drag length in view units per unit of velocity, as when spawning bodies.
+const DRAG_PER_VELOCITY: f32 = 50.0;
+/// Ctrl+Left/Right rotation speed of a pending paste, in radians per second.
+const ROTATE_SPEED: f32 = std::f32::consts::FRAC_PI_2;
+
+/// A paste waiting to be placed by the next left click.
+struct Paste {
+    /// Rotation applied to positions and velocities, in radians.
+    rotation: f32,
+    /// Where the left button went down, in view coordinates. Dragging from
+    /// here sets the velocity offset.
+    anchor: Option<Vec2>,
+}
+
+/// Copied bodies, stored relative to the center of mass of the copy so
+/// pastes keep their relative positions and velocities.
+#[derive(Resource, Default)]
+pub struct Clipboard {
+    bodies: Vec<(Body, Option<GasParticle>)>,
+    /// Center of mass velocity of the copied group.
+    velocity: Vec2,
+    paste: Option<Paste>,
+}
+
+impl Clipboard {
+    /// Whether left clicks currently place a paste.
+    pub fn pasting(&self) -> bool {
+        self.paste.is_some()
+    }
+
+    fn copy<'a>(&mut self, bodies: impl Iterator<Item = (&'a Body, Option<&'a GasParticle>)>) {
+        let bodies = bodies.collect::<Vec<_>>();
+        let Some(center) = CenterOfMass::of(bodies.iter().map(|(body, _)| *body)) else {
+            return;
+        };
+        self.velocity = center.vel;
+        self.bodies = bodies
+            .into_iter()
+            .map(|(body, gas)| {
+                let mut copy = Body::new(
+                    body.x - center.pos.x,
+                    body.y - center.pos.y,
+                    body.v_x - center.vel.x,
+                    body.v_y - center.vel.y,
+                    body.density,
+                    body.size,
+                );
+                copy.color = body.color;
+                (copy, gas.map(|gas| GasParticle::new(gas.smoothing_length)))
+            })
+            .collect();
+    }
+
+    /// The copied bodies rotated and moved to `center` with the group moving
+    /// at `velocity`, in world coordinates.
+    fn placed(
+        &self,
+        center: Vec2,
+        velocity: Vec2,
+        rotation: f32,
+    ) -> impl Iterator<Item = (Body, Option<GasParticle>)> + '_ {
+        let rotation = Vec2::from_angle(rotation);
+        self.bodies.iter().map(move |(body, gas)| {
+            let pos = center + rotation.rotate(Vec2::new(body.x, body.y));
+            let vel = velocity + rotation.rotate(Vec2::new(body.v_x, body.v_y));
+            let mut placed = *body;
+            placed.x = pos.x;
+            placed.y = pos.y;
+            placed.v_x = vel.x;
+            placed.v_y = vel.y;
+            (placed, *gas)
+        })
+    }
+}
+
+/// Ctrl+C copies the selection and Ctrl+V arms a paste; Ctrl+D does both.
+/// While armed, the copy follows the cursor, Ctrl+Left/Right rotate it,
+/// a click places it and dragging before release adds a velocity offset.
+/// Esc cancels. Pasted bodies become the new selection.
+#[allow(clippy::too_many_arguments)]
+pub fn clipboard_system(
+    mut commands: Commands,
+    mut gizmos: Gizmos,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut clipboard: ResMut<Clipboard>,
+    mut selection: ResMut<Selection>,
+    bodies: Query<(Entity, &Body, Option<&GasParticle>)>,
+    frame: Res<ViewFrame>,
+    body_assets: Res<BodyAssets>,
+    mut recorder: HistoryRecorder,
+    mut diagnostics: ResMut<ConservationDiagnostics>,
+    time: Res<Time>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if ctrl && keyboard_input.any_just_pressed([KeyCode::KeyC, KeyCode::KeyD]) {
+        let selected = bodies.iter_many(&selection.entities);
+        clipboard.copy(selected.map(|(_, body, gas)| (body, gas)));
+    }
+    if ctrl
+        && keyboard_input.any_just_pressed([KeyCode::KeyV, KeyCode::KeyD])
+        && !clipboard.bodies.is_empty()
+    {
+        clipboard.paste = Some(Paste {
+            rotation: 0.0,
+            anchor: None,
+        });
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        clipboard.paste = None;
+    }
+    let Some(paste) = clipboard.paste.as_mut() else {
+        return;
+    };
+    if ctrl {
+        if keyboard_input.pressed(KeyCode::ArrowLeft) {
+            paste.rotation += ROTATE_SPEED * time.delta_seconds();
+        }
+        if keyboard_input.pressed(KeyCode::ArrowRight) {
+            paste.rotation -= ROTATE_SPEED * time.delta_seconds();
+        }
+    }
+
+    let window = windows.single();
+    let (camera, camera_transform) = camera_query.single();
+    let Some(cursor) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+    if mouse_button_input.just_pressed(MouseButton::Left) {
+        paste.anchor = Some(cursor);
+    }
+
+    let (rotation, anchor) = (paste.rotation, paste.anchor);
+    let (center, velocity) = match anchor {
+        Some(anchor) => (anchor, (cursor - anchor) / DRAG_PER_VELOCITY),
+        None => (cursor, Vec2::ZERO),
+    };
+    let world_center = frame.transform.to_world(center);
+    let world_velocity = clipboard.velocity + frame.transform.vector_to_world(velocity);
+
+    if anchor.is_some() && mouse_button_input.just_released(MouseButton::Left) {
+        selection.clear();
+        let mut spawned = Vec::with_capacity(clipboard.bodies.len());
+        for (body, gas) in clipboard.placed(world_center, world_velocity, rotation) {
+            let mut entity = spawn_body(&mut commands, &body_assets, body);
+            if let Some(gas) = gas {
+                entity.insert(gas);
+            }
+            spawned.push(entity.id());
+        }
+        // Freshly spawned, so no duplicates to check for
+        selection.entities.extend(&spawned);
+        recorder.spawned(spawned);
+        clipboard.paste = None;
+        // New bodies are not drift
+        diagnostics.rebaseline();
+        return;
+    }
+
+    // Ghost of the paste
+    let ghost = Color::rgba(1.0, 1.0, 1.0, 0.4);
+    for (body, _) in clipboard.placed(world_center, world_velocity, rotation) {
+        let pos = frame.transform.to_view(Vec2::new(body.x, body.y));
+        gizmos.circle_2d(pos, body.size, ghost);
+    }
+    if anchor.is_some() {
+        gizmos.arrow_2d(center, cursor, ghost);
+    }
+}
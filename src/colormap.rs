@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+/// Maps a value in `0..=1` to a color, for heatmaps and color-by-quantity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    Inferno,
+    Coolwarm,
+    Grayscale,
+}
+
+impl Colormap {
+    pub fn next(self) -> Self {
+        match self {
+            Colormap::Viridis => Colormap::Inferno,
+            Colormap::Inferno => Colormap::Coolwarm,
+            Colormap::Coolwarm => Colormap::Grayscale,
+            Colormap::Grayscale => Colormap::Viridis,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Colormap::Viridis => "VIRIDIS",
+            Colormap::Inferno => "INFERNO",
+            Colormap::Coolwarm => "COOLWARM",
+            Colormap::Grayscale => "GRAYSCALE",
+        }
+    }
+
+    /// Evenly spaced control points, interpolated linearly in sRGB.
+    fn stops(self) -> &'static [[f32; 3]] {
+        match self {
+            Colormap::Viridis => &[
+                [0.267, 0.005, 0.329],
+                [0.229, 0.322, 0.546],
+                [0.128, 0.567, 0.551],
+                [0.369, 0.789, 0.383],
+                [0.993, 0.906, 0.144],
+            ],
+            Colormap::Inferno => &[
+                [0.001, 0.000, 0.014],
+                [0.342, 0.062, 0.429],
+                [0.735, 0.216, 0.330],
+                [0.978, 0.557, 0.035],
+                [0.988, 0.998, 0.645],
+            ],
+            Colormap::Coolwarm => &[
+                [0.230, 0.299, 0.754],
+                [0.552, 0.690, 0.996],
+                [0.866, 0.866, 0.866],
+                [0.956, 0.604, 0.486],
+                [0.706, 0.016, 0.150],
+            ],
+            Colormap::Grayscale => &[[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]],
+        }
+    }
+
+    /// The color at `t`, clamped to `0..=1`.
+    pub fn sample(self, t: f32) -> Color {
+        let [r, g, b] = self.sample_rgb(t);
+        Color::rgb(r, g, b)
+    }
+
+    pub fn sample_rgb(self, t: f32) -> [f32; 3] {
+        let stops = self.stops();
+        let x = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let i = (x as usize).min(stops.len() - 2);
+        let f = x - i as f32;
+        let (a, b) = (stops[i], stops[i + 1]);
+        [
+            a[0] + (b[0] - a[0]) * f,
+            a[1] + (b[1] - a[1]) * f,
+            a[2] + (b[2] - a[2]) * f,
+        ]
+    }
+}
@@ -0,0 +1,236 @@
+use bevy::math::DVec2;
+use bevy::prelude::*;
+
+use crate::GRAVITY_CONST;
+use crate::body::Body;
+
+/// Above this many bodies the O(N^2) potential energy sum is skipped.
+const MAX_POTENTIAL_BODIES: usize = 2_000;
+/// Seconds between potential energy sums; the last sum is reused in between.
+const POTENTIAL_INTERVAL: f32 = 0.25;
+
+/// Conserved quantities of the whole system at one step.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Conserved {
+    pub kinetic: f64,
+    /// `None` when there are too many bodies to sum pairwise. Only summed
+    /// every `POTENTIAL_INTERVAL`, so it can lag a few steps behind.
+    pub potential: Option<f64>,
+    /// Kinetic plus potential energy at the step the potential was summed,
+    /// so the two halves always come from the same state.
+    pub energy: Option<f64>,
+    pub momentum: DVec2,
+    pub angular_momentum: f64,
+    pub center_of_mass: DVec2,
+    pub total_mass: f64,
+    /// Sum of `m |v|`, the scale momentum drift is measured against.
+    pub momentum_scale: f64,
+    /// Sum of `m |r x v|`, the scale angular momentum drift is measured against.
+    pub angular_momentum_scale: f64,
+}
+
+impl Conserved {
+    /// Measures everything but the potential, which is left to
+    /// [`Conserved::sum_potential`].
+    pub fn measure(bodies: &[&Body]) -> Self {
+        let mut c = Conserved::default();
+
+        for body in bodies {
+            let mass = body.mass as f64;
+            let pos = DVec2::new(body.x as f64, body.y as f64);
+            let vel = DVec2::new(body.v_x as f64, body.v_y as f64);
+            c.total_mass += mass;
+            c.center_of_mass += pos * mass;
+            c.kinetic += 0.5 * mass * vel.length_squared();
+            c.momentum += vel * mass;
+            c.momentum_scale += mass * vel.length();
+        }
+        if c.total_mass > 0.0 {
+            c.center_of_mass /= c.total_mass;
+        }
+
+        // Angular momentum about the origin, scaled about the center of mass
+        for body in bodies {
+            let mass = body.mass as f64;
+            let pos = DVec2::new(body.x as f64, body.y as f64);
+            let vel = DVec2::new(body.v_x as f64, body.v_y as f64);
+            c.angular_momentum += mass * pos.perp_dot(vel);
+            c.angular_momentum_scale += mass * (pos - c.center_of_mass).perp_dot(vel).abs();
+        }
+
+        c
+    }
+
+    /// Sums the pairwise potential energy of `bodies`, which must be the
+    /// ones this was measured from.
+    pub fn sum_potential(&mut self, bodies: &[&Body]) {
+        self.potential = None;
+        if bodies.len() <= MAX_POTENTIAL_BODIES {
+            let mut potential = 0.0;
+            for i in 0..bodies.len() {
+                for j in (i + 1)..bodies.len() {
+                    let dx = (bodies[j].x - bodies[i].x) as f64;
+                    let dy = (bodies[j].y - bodies[i].y) as f64;
+                    let distance = (dx * dx + dy * dy).sqrt().max(0.0001);
+                    potential -=
+                        GRAVITY_CONST as f64 * bodies[i].mass as f64 * bodies[j].mass as f64
+                            / distance;
+                }
+            }
+            self.potential = Some(potential);
+        }
+        self.energy = self.potential.map(|potential| self.kinetic + potential);
+    }
+
+    pub fn total_energy(&self) -> Option<f64> {
+        self.energy
+    }
+}
+
+/// Conserved quantities for the current step along with the values they had
+/// when the set of bodies last changed, so drift can be judged at a glance.
+#[derive(Resource)]
+pub struct ConservationDiagnostics {
+    pub current: Option<Conserved>,
+    pub initial: Option<Conserved>,
+    potential_timer: Timer,
+}
+
+impl Default for ConservationDiagnostics {
+    fn default() -> Self {
+        ConservationDiagnostics {
+            current: None,
+            initial: None,
+            potential_timer: Timer::from_seconds(POTENTIAL_INTERVAL, TimerMode::Repeating),
+        }
+    }
+}
+
+impl ConservationDiagnostics {
+    /// Takes a new baseline on the next measurement, e.g. after the user
+    /// edits bodies by hand.
+    pub fn rebaseline(&mut self) {
+        self.initial = None;
+    }
+
+    /// Relative drift of the total energy from its initial value.
+    pub fn energy_drift(&self) -> Option<f64> {
+        let initial = self.initial?.total_energy()?;
+        let current = self.current?.total_energy()?;
+        relative(current - initial, initial.abs())
+    }
+
+    /// Change in linear momentum relative to the initial `sum(m |v|)`.
+    pub fn momentum_drift(&self) -> Option<f64> {
+        let (initial, current) = (self.initial?, self.current?);
+        relative(
+            (current.momentum - initial.momentum).length(),
+            initial.momentum_scale,
+        )
+    }
+
+    /// Change in angular momentum relative to the initial `sum(m |r x v|)`.
+    pub fn angular_momentum_drift(&self) -> Option<f64> {
+        let (initial, current) = (self.initial?, self.current?);
+        relative(
+            current.angular_momentum - initial.angular_momentum,
+            initial.angular_momentum_scale,
+        )
+    }
+}
+
+fn relative(delta: f64, scale: f64) -> Option<f64> {
+    (scale > 0.0).then(|| delta / scale)
+}
+
+#[derive(Component)]
+pub struct DiagnosticsText;
+
+pub fn diagnostics_hud_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/start.ttf");
+
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font,
+                    font_size: 12.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..default()
+            }),
+        )
+        .insert(DiagnosticsText);
+}
+
+/// Measures the conserved quantities every step, taking a new baseline
+/// whenever bodies are spawned or despawned. The potential energy is summed
+/// a few times per second and on every new baseline.
+pub fn conservation_diagnostics_system(
+    query: Query<&Body>,
+    added: Query<(), Added<Body>>,
+    mut removed: RemovedComponents<Body>,
+    mut diagnostics: ResMut<ConservationDiagnostics>,
+    time: Res<Time>,
+) {
+    let bodies = query.iter().collect::<Vec<_>>();
+    let changed = !added.is_empty() || removed.read().count() > 0;
+    let rebaseline = changed || diagnostics.initial.is_none();
+    diagnostics.potential_timer.tick(time.delta());
+
+    let mut current = Conserved::measure(&bodies);
+    match diagnostics.current {
+        Some(previous) if !rebaseline && !diagnostics.potential_timer.just_finished() => {
+            current.potential = previous.potential;
+            current.energy = previous.energy;
+        }
+        _ => current.sum_potential(&bodies),
+    }
+    diagnostics.current = Some(current);
+    if rebaseline {
+        diagnostics.initial = Some(current);
+    }
+}
+
+pub fn diagnostics_hud_update_system(
+    mut query: Query<&mut Text, With<DiagnosticsText>>,
+    diagnostics: Res<ConservationDiagnostics>,
+) {
+    let Some(current) = diagnostics.current else {
+        return;
+    };
+    let fmt = |value: Option<f64>| match value {
+        Some(value) => format!("{:+.3E}", value),
+        None => "N/A".to_string(),
+    };
+    // The energies only change when the potential is summed, so say how often
+    let sampling = match current.potential {
+        Some(_) => format!("SAMPLED EVERY {POTENTIAL_INTERVAL}S"),
+        None => format!("OVER {MAX_POTENTIAL_BODIES} BODIES"),
+    };
+
+    let text = format!(
+        "KE: {}\nPE: {} ({})\nE: {} (DRIFT {})\nP: ({}, {}) (DRIFT {})\nL: {} (DRIFT {})\nCOM: ({:.1}, {:.1})",
+        fmt(Some(current.kinetic)),
+        fmt(current.potential),
+        sampling,
+        fmt(current.total_energy()),
+        fmt(diagnostics.energy_drift()),
+        fmt(Some(current.momentum.x)),
+        fmt(Some(current.momentum.y)),
+        fmt(diagnostics.momentum_drift()),
+        fmt(Some(current.angular_momentum)),
+        fmt(diagnostics.angular_momentum_drift()),
+        current.center_of_mass.x,
+        current.center_of_mass.y,
+    );
+    for mut hud_text in query.iter_mut() {
+        hud_text.sections[0].value = text.clone();
+    }
+}
@@ -0,0 +1,457 @@
+use bevy::math::DVec2;
+use bevy::prelude::*;
+
+use crate::GRAVITY_CONST;
+
+/// Highest expansion order the solver accepts.
+pub const MAX_ORDER: usize = 8;
+/// Deepest quadtree level; bounds memory for very large scenes.
+const MAX_DEPTH: usize = 8;
+
+/// Fast multipole gravity solver on a uniform quadtree. Multipole and local
+/// expansions are Cartesian Taylor series of the `1 / r` kernel truncated at
+/// `order`, so raising the order trades speed for accuracy.
+#[derive(Resource)]
+pub struct Fmm {
+    /// Highest order kept in the multipole and local expansions.
+    pub order: usize,
+    /// Target number of bodies per leaf box; sets the tree depth.
+    pub leaf_size: usize,
+}
+
+impl Default for Fmm {
+    fn default() -> Self {
+        Fmm {
+            order: 4,
+            leaf_size: 16,
+        }
+    }
+}
+
+impl Fmm {
+    /// Steps the expansion order through `1..=MAX_ORDER`.
+    pub fn cycle_order(&mut self) {
+        self.order = self.order % MAX_ORDER + 1;
+    }
+
+    /// Computes the acceleration of every `(position, mass)` pair.
+    pub fn accelerations(&self, bodies: &[(Vec2, f32)]) -> Vec<Vec2> {
+        let n = bodies.len();
+        if n < 2 {
+            return vec![Vec2::ZERO; n];
+        }
+
+        let p = self.order.clamp(1, MAX_ORDER);
+        let terms = Terms::new(p);
+        let deriv_terms = Terms::new(2 * p);
+        let binom = binomials(2 * p);
+
+        let points: Vec<DVec2> = bodies.iter().map(|(pos, _)| pos.as_dvec2()).collect();
+        let masses: Vec<f64> = bodies.iter().map(|&(_, mass)| mass as f64).collect();
+
+        // Bounding square of every body
+        let mut min = points[0];
+        let mut max = points[0];
+        for pt in &points {
+            min = min.min(*pt);
+            max = max.max(*pt);
+        }
+        let size = (max - min).max_element().max(1.0) * 1.0001;
+
+        let mut depth = 2;
+        while depth < MAX_DEPTH && n > self.leaf_size.max(1) << (2 * depth) {
+            depth += 1;
+        }
+        let tree = Tree { origin: min, size };
+
+        // Sort bodies into leaves
+        let leaf_side = 1usize << depth;
+        let leaf_of: Vec<usize> = points
+            .iter()
+            .map(|pt| {
+                let cell = ((*pt - min) / size * leaf_side as f64).floor();
+                let ix = (cell.x as usize).min(leaf_side - 1);
+                let iy = (cell.y as usize).min(leaf_side - 1);
+                iy * leaf_side + ix
+            })
+            .collect();
+        let mut start = vec![0usize; leaf_side * leaf_side + 1];
+        for &leaf in &leaf_of {
+            start[leaf + 1] += 1;
+        }
+        for i in 0..leaf_side * leaf_side {
+            start[i + 1] += start[i];
+        }
+        let mut fill = start.clone();
+        let mut sorted = vec![0usize; n];
+        for (i, &leaf) in leaf_of.iter().enumerate() {
+            sorted[fill[leaf]] = i;
+            fill[leaf] += 1;
+        }
+
+        // Body counts per box on every level, used to skip empty boxes
+        let mut counts: Vec<Vec<usize>> = (0..=depth).map(|l| vec![0; 1 << (2 * l)]).collect();
+        for leaf in 0..leaf_side * leaf_side {
+            counts[depth][leaf] = start[leaf + 1] - start[leaf];
+        }
+        for l in (0..depth).rev() {
+            let side = 1usize << l;
+            for iy in 0..side {
+                for ix in 0..side {
+                    counts[l][iy * side + ix] =
+                        children(ix, iy, side * 2).map(|c| counts[l + 1][c]).sum();
+                }
+            }
+        }
+
+        let t = terms.len();
+        let mut multipoles: Vec<Vec<f64>> = (0..=depth).map(|l| vec![0.0; t << (2 * l)]).collect();
+        let mut locals = multipoles.clone();
+
+        // P2M: leaf multipoles from the bodies they contain
+        for iy in 0..leaf_side {
+            for ix in 0..leaf_side {
+                let leaf = iy * leaf_side + ix;
+                let center = tree.center(depth, ix, iy);
+                let m = &mut multipoles[depth][leaf * t..(leaf + 1) * t];
+                for &i in &sorted[start[leaf]..start[leaf + 1]] {
+                    let (px, py) = powers(points[i] - center, p);
+                    for (k, &(a, b)) in terms.list.iter().enumerate() {
+                        m[k] += masses[i] * px[a] * py[b];
+                    }
+                }
+            }
+        }
+
+        // M2M: shift child multipoles up to their parents
+        for l in (2..depth).rev() {
+            let side = 1usize << l;
+            for iy in 0..side {
+                for ix in 0..side {
+                    let parent = iy * side + ix;
+                    if counts[l][parent] == 0 {
+                        continue;
+                    }
+                    let center = tree.center(l, ix, iy);
+                    let mut shifted = vec![0.0; t];
+                    for (cx, cy) in child_coords(ix, iy) {
+                        let child = cy * side * 2 + cx;
+                        if counts[l + 1][child] == 0 {
+                            continue;
+                        }
+                        let (sx, sy) = powers(tree.center(l + 1, cx, cy) - center, p);
+                        let m = &multipoles[l + 1][child * t..(child + 1) * t];
+                        for (k, &(ka, kb)) in terms.list.iter().enumerate() {
+                            for i in 0..=ka {
+                                for j in 0..=kb {
+                                    shifted[k] += binom[ka][i]
+                                        * binom[kb][j]
+                                        * sx[ka - i]
+                                        * sy[kb - j]
+                                        * m[terms.index(i, j)];
+                                }
+                            }
+                        }
+                    }
+                    multipoles[l][parent * t..(parent + 1) * t].copy_from_slice(&shifted);
+                }
+            }
+        }
+
+        // M2L: convert well-separated multipoles into local expansions
+        for l in 2..=depth {
+            let side = 1usize << l;
+            for iy in 0..side {
+                for ix in 0..side {
+                    let target = iy * side + ix;
+                    if counts[l][target] == 0 {
+                        continue;
+                    }
+                    let center = tree.center(l, ix, iy);
+                    let mut local = vec![0.0; t];
+                    for (px, py) in neighbours(ix / 2, iy / 2, side / 2) {
+                        for (cx, cy) in child_coords(px, py) {
+                            let source = cy * side + cx;
+                            if counts[l][source] == 0 || adjacent(ix, iy, cx, cy) {
+                                continue;
+                            }
+                            let derivs = derivatives(center - tree.center(l, cx, cy), &deriv_terms);
+                            let m = &multipoles[l][source * t..(source + 1) * t];
+                            for (ni, &(na, nb)) in terms.list.iter().enumerate() {
+                                let mut sum = 0.0;
+                                for (ki, &(ka, kb)) in terms.list.iter().enumerate() {
+                                    sum += binom[na + ka][na]
+                                        * binom[nb + kb][nb]
+                                        * m[ki]
+                                        * derivs[deriv_terms.index(na + ka, nb + kb)];
+                                }
+                                let sign = if (na + nb) % 2 == 0 { 1.0 } else { -1.0 };
+                                local[ni] += sign * sum;
+                            }
+                        }
+                    }
+                    for (acc, value) in locals[l][target * t..(target + 1) * t]
+                        .iter_mut()
+                        .zip(local)
+                    {
+                        *acc += value;
+                    }
+                }
+            }
+        }
+
+        // L2L: push local expansions down to the children
+        for l in 2..depth {
+            let side = 1usize << l;
+            for iy in 0..side {
+                for ix in 0..side {
+                    let parent = iy * side + ix;
+                    if counts[l][parent] == 0 {
+                        continue;
+                    }
+                    let center = tree.center(l, ix, iy);
+                    let local = locals[l][parent * t..(parent + 1) * t].to_vec();
+                    for (cx, cy) in child_coords(ix, iy) {
+                        let child = cy * side * 2 + cx;
+                        if counts[l + 1][child] == 0 {
+                            continue;
+                        }
+                        let (sx, sy) = powers(tree.center(l + 1, cx, cy) - center, p);
+                        let out = &mut locals[l + 1][child * t..(child + 1) * t];
+                        for (mi, &(ma, mb)) in terms.list.iter().enumerate() {
+                            for (ni, &(na, nb)) in terms.list.iter().enumerate() {
+                                if na >= ma && nb >= mb {
+                                    out[mi] += binom[na][ma]
+                                        * binom[nb][mb]
+                                        * sx[na - ma]
+                                        * sy[nb - mb]
+                                        * local[ni];
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut accelerations = vec![DVec2::ZERO; n];
+        for iy in 0..leaf_side {
+            for ix in 0..leaf_side {
+                let leaf = iy * leaf_side + ix;
+                let members = &sorted[start[leaf]..start[leaf + 1]];
+                if members.is_empty() {
+                    continue;
+                }
+
+                // L2P: far field from the leaf's local expansion
+                let center = tree.center(depth, ix, iy);
+                let local = &locals[depth][leaf * t..(leaf + 1) * t];
+                for &i in members {
+                    let (ex, ey) = powers(points[i] - center, p);
+                    let mut grad = DVec2::ZERO;
+                    for (k, &(a, b)) in terms.list.iter().enumerate() {
+                        if a > 0 {
+                            grad.x += a as f64 * local[k] * ex[a - 1] * ey[b];
+                        }
+                        if b > 0 {
+                            grad.y += b as f64 * local[k] * ex[a] * ey[b - 1];
+                        }
+                    }
+                    accelerations[i] += grad;
+                }
+
+                // P2P: near field summed directly over adjacent leaves
+                for (jx, jy) in neighbours(ix, iy, leaf_side) {
+                    let other = jy * leaf_side + jx;
+                    for &i in members {
+                        for &j in &sorted[start[other]..start[other + 1]] {
+                            if i == j {
+                                continue;
+                            }
+                            let direction = points[j] - points[i];
+                            let distance = direction.length().max(0.0001);
+                            accelerations[i] += direction * masses[j] / distance.powi(3);
+                        }
+                    }
+                }
+            }
+        }
+
+        accelerations
+            .into_iter()
+            .map(|acc| (acc * GRAVITY_CONST as f64).as_vec2())
+            .collect()
+    }
+}
+
+struct Tree {
+    origin: DVec2,
+    size: f64,
+}
+
+impl Tree {
+    fn center(&self, level: usize, ix: usize, iy: usize) -> DVec2 {
+        let width = self.size / (1usize << level) as f64;
+        self.origin + DVec2::new(ix as f64 + 0.5, iy as f64 + 0.5) * width
+    }
+}
+
+/// Multi-indices `(a, b)` with `a + b <= order`, ordered by total degree.
+struct Terms {
+    order: usize,
+    list: Vec<(usize, usize)>,
+}
+
+impl Terms {
+    fn new(order: usize) -> Self {
+        let mut list = Vec::new();
+        for total in 0..=order {
+            for a in (0..=total).rev() {
+                list.push((a, total - a));
+            }
+        }
+        Terms { order, list }
+    }
+
+    fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    fn index(&self, a: usize, b: usize) -> usize {
+        let total = a + b;
+        debug_assert!(total <= self.order);
+        total * (total + 1) / 2 + (total - a)
+    }
+}
+
+fn binomials(n: usize) -> Vec<Vec<f64>> {
+    let mut table = vec![vec![0.0; n + 1]; n + 1];
+    for i in 0..=n {
+        table[i][0] = 1.0;
+        for j in 1..=i {
+            table[i][j] = table[i - 1][j - 1] + if j < i { table[i - 1][j] } else { 0.0 };
+        }
+    }
+    table
+}
+
+fn powers(v: DVec2, order: usize) -> (Vec<f64>, Vec<f64>) {
+    let mut px = vec![1.0; order + 1];
+    let mut py = vec![1.0; order + 1];
+    for i in 1..=order {
+        px[i] = px[i - 1] * v.x;
+        py[i] = py[i - 1] * v.y;
+    }
+    (px, py)
+}
+
+/// Taylor coefficients `(1/k!) d^k/dy^k (1 / |x - y|)` at separation
+/// `r = x - y`, built with the standard recurrence for the Laplace kernel.
+fn derivatives(r: DVec2, terms: &Terms) -> Vec<f64> {
+    let r2 = r.length_squared();
+    let mut a = vec![0.0; terms.len()];
+    a[0] = 1.0 / r2.sqrt();
+    for (k, &(ax, ay)) in terms.list.iter().enumerate().skip(1) {
+        let total = (ax + ay) as f64;
+        let mut value = 0.0;
+        if ax >= 1 {
+            value += (2.0 * total - 1.0) * r.x * a[terms.index(ax - 1, ay)];
+        }
+        if ay >= 1 {
+            value += (2.0 * total - 1.0) * r.y * a[terms.index(ax, ay - 1)];
+        }
+        if ax >= 2 {
+            value -= (total - 1.0) * a[terms.index(ax - 2, ay)];
+        }
+        if ay >= 2 {
+            value -= (total - 1.0) * a[terms.index(ax, ay - 2)];
+        }
+        a[k] = value / (total * r2);
+    }
+    a
+}
+
+fn children(ix: usize, iy: usize, child_side: usize) -> impl Iterator<Item = usize> {
+    child_coords(ix, iy).map(move |(cx, cy)| cy * child_side + cx)
+}
+
+fn child_coords(ix: usize, iy: usize) -> impl Iterator<Item = (usize, usize)> {
+    [(0, 0), (1, 0), (0, 1), (1, 1)]
+        .into_iter()
+        .map(move |(dx, dy)| (ix * 2 + dx, iy * 2 + dy))
+}
+
+/// The box itself and every box touching it on a `side` x `side` level.
+fn neighbours(ix: usize, iy: usize, side: usize) -> impl Iterator<Item = (usize, usize)> {
+    let xs = ix.saturating_sub(1)..=(ix + 1).min(side - 1);
+    let ys = iy.saturating_sub(1)..=(iy + 1).min(side - 1);
+    ys.flat_map(move |y| xs.clone().map(move |x| (x, y)))
+}
+
+fn adjacent(ax: usize, ay: usize, bx: usize, by: usize) -> bool {
+    ax.abs_diff(bx) <= 1 && ay.abs_diff(by) <= 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{pairwise_accelerations, rms_force_error};
+
+    /// Bodies scattered over a square with uneven masses, enough to need
+    /// several tree levels.
+    fn sample_bodies() -> Vec<(Vec2, f32)> {
+        let mut state = 12345u32;
+        let mut random = move || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (state >> 8) as f32 / (1 << 24) as f32
+        };
+        (0..2000)
+            .map(|_| {
+                let pos = Vec2::new(random(), random()) * 1000.0;
+                (pos, 1.0 + random() * 100.0)
+            })
+            .collect()
+    }
+
+    fn error_at(order: usize, bodies: &[(Vec2, f32)], direct: &[Vec2]) -> f32 {
+        let fmm = Fmm { order, ..default() };
+        rms_force_error(bodies, &fmm.accelerations(bodies), direct)
+    }
+
+    #[test]
+    fn error_falls_with_order() {
+        let bodies = sample_bodies();
+        let direct = pairwise_accelerations(&bodies);
+        let errors: Vec<f32> = (1..=MAX_ORDER)
+            .map(|order| error_at(order, &bodies, &direct))
+            .collect();
+
+        assert!(errors[0] < 1e-2, "order 1 error {}", errors[0]);
+        assert!(
+            errors[MAX_ORDER - 1] < 1e-5,
+            "order 8 error {}",
+            errors[MAX_ORDER - 1]
+        );
+        for pair in errors.windows(2) {
+            assert!(pair[1] < pair[0], "error rose with order: {errors:?}");
+        }
+    }
+
+    #[test]
+    fn no_force_without_a_second_body() {
+        let fmm = Fmm::default();
+        assert!(fmm.accelerations(&[]).is_empty());
+        assert_eq!(
+            fmm.accelerations(&[(Vec2::new(3.0, 4.0), 10.0)]),
+            [Vec2::ZERO]
+        );
+    }
+
+    #[test]
+    fn coincident_bodies_match_direct_sum() {
+        let bodies = [(Vec2::new(5.0, 5.0), 10.0), (Vec2::new(5.0, 5.0), 20.0)];
+        let direct = pairwise_accelerations(&bodies);
+        assert_eq!(Fmm::default().accelerations(&bodies), direct);
+        assert_eq!(direct, [Vec2::ZERO; 2]);
+    }
+}
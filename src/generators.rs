@@ -0,0 +1,505 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::prelude::*;
+use rustfft::FftPlanner;
+use rustfft::num_complex::Complex;
+
+use crate::GRAVITY_CONST;
+use crate::body::{Body, BodyAssets, spawn_body};
+use crate::history::HistoryRecorder;
+use crate::orbit;
+use crate::particle_mesh::fft_2d;
+use crate::selection::Selection;
+use crate::view_frame::ViewFrame;
+
+/// Side of the grid the random field is generated on.
+const FIELD_GRID: usize = 64;
+/// Standard deviation of the log-density of the random field.
+const FIELD_CONTRAST: f32 = 1.5;
+
+/// A procedural arrangement of many bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generator {
+    /// Uniform disk rotating at the circular velocity of the mass inside.
+    Disk,
+    /// Plummer sphere, scaled into virial equilibrium.
+    Plummer,
+    /// Thin ring orbiting the selected body, or the strongest attractor.
+    Ring,
+    /// Two-armed spiral disk around a hot bulge, inside a sparse halo.
+    Galaxy,
+    /// Cold bodies sampled from a log-normal random field with a power-law
+    /// spectrum.
+    RandomField,
+}
+
+impl Generator {
+    pub fn label(self) -> &'static str {
+        match self {
+            Generator::Disk => "DISK",
+            Generator::Plummer => "PLUMMER",
+            Generator::Ring => "RING",
+            Generator::Galaxy => "GALAXY",
+            Generator::RandomField => "RANDOM FIELD",
+        }
+    }
+}
+
+/// How body masses are drawn. Bodies share a density, so mass sets size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MassDistribution {
+    #[default]
+    Equal,
+    /// Uniform between 0.2 and 1.8 times the base mass.
+    Uniform,
+    /// Salpeter power law between 0.1 and 10 times the base mass, so most
+    /// bodies are light and a few are heavy.
+    Salpeter,
+}
+
+impl MassDistribution {
+    pub fn next(self) -> Self {
+        match self {
+            MassDistribution::Equal => MassDistribution::Uniform,
+            MassDistribution::Uniform => MassDistribution::Salpeter,
+            MassDistribution::Salpeter => MassDistribution::Equal,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MassDistribution::Equal => "EQUAL",
+            MassDistribution::Uniform => "UNIFORM",
+            MassDistribution::Salpeter => "SALPETER",
+        }
+    }
+
+    /// A mass as a multiple of the base mass.
+    fn sample(self, rng: &mut Rng) -> f32 {
+        match self {
+            MassDistribution::Equal => 1.0,
+            MassDistribution::Uniform => rng.range(0.2, 1.8),
+            MassDistribution::Salpeter => {
+                let (lo, hi, exponent) = (0.1f32, 10.0f32, 1.0 - 2.35);
+                let (a, b) = (lo.powf(exponent), hi.powf(exponent));
+                (a + rng.f32() * (b - a)).powf(1.0 / exponent)
+            }
+        }
+    }
+}
+
+/// Parameters shared by every generator.
+#[derive(Resource)]
+pub struct GeneratorSettings {
+    pub count: usize,
+    /// Overall radius of the generated structure in world units.
+    pub radius: f32,
+    pub mass: MassDistribution,
+    /// Size of a body of base mass.
+    pub body_size: f32,
+    pub density: f32,
+    /// The same seed always generates the same bodies.
+    pub seed: u64,
+    /// Power-law index of the random field spectrum; more negative values
+    /// give larger clumps.
+    pub spectral_index: f32,
+    /// Last generator run, for the HUD.
+    pub last: Option<Generator>,
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> Self {
+        GeneratorSettings {
+            count: 200,
+            radius: 300.0,
+            mass: MassDistribution::Equal,
+            body_size: 3.0,
+            density: 1.0,
+            seed: 1,
+            spectral_index: -3.0,
+            last: None,
+        }
+    }
+}
+
+impl GeneratorSettings {
+    fn body(&self, rng: &mut Rng, pos: Vec2) -> Body {
+        let size = self.body_size * self.mass.sample(rng).cbrt();
+        Body::new(pos.x, pos.y, 0.0, 0.0, self.density, size)
+    }
+
+    /// Bodies of `generator` around the origin. Rings orbit a body of
+    /// `central_mass` at the origin; the other generators ignore it.
+    fn generate(&self, generator: Generator, central_mass: f32) -> Vec<Body> {
+        let mut rng = Rng::new(self.seed);
+        let count = self.count.max(1);
+        let radius = self.radius;
+        match generator {
+            Generator::Disk => {
+                let mut bodies = (0..count)
+                    .map(|_| {
+                        let pos = rng_direction(&mut rng) * radius * rng.f32().sqrt();
+                        self.body(&mut rng, pos)
+                    })
+                    .collect::<Vec<_>>();
+                set_circular_velocities(&mut bodies, Vec2::ZERO, 0.0);
+                bodies
+            }
+            Generator::Plummer => {
+                let mut bodies = plummer(self, &mut rng, count, radius / 3.0, radius * 2.0);
+                virialize(&mut bodies);
+                bodies
+            }
+            Generator::Ring => {
+                let mut bodies = (0..count)
+                    .map(|_| {
+                        let pos = rng_direction(&mut rng) * radius * rng.range(0.95, 1.05);
+                        self.body(&mut rng, pos)
+                    })
+                    .collect::<Vec<_>>();
+                set_circular_velocities(&mut bodies, Vec2::ZERO, central_mass);
+                bodies
+            }
+            Generator::Galaxy => {
+                let bulge_count = count / 5;
+                let halo_count = count / 10;
+                let disk_count = count - bulge_count - halo_count;
+                let scale_length = radius / 3.0;
+                // Tight log spirals: the angle grows with ln(r)
+                let winding = 1.0 / 15f32.to_radians().tan();
+
+                let mut bodies = (0..disk_count)
+                    .map(|i| {
+                        // Exponential profile, cut off at the galaxy radius
+                        let r = (-scale_length * (1.0 - rng.f32()).ln()).min(radius);
+                        let arm = (i % 2) as f32 * PI;
+                        let angle = arm
+                            + winding * (r / scale_length).max(0.05).ln()
+                            + rng.gaussian() * 0.3;
+                        self.body(&mut rng, Vec2::from_angle(angle) * r)
+                    })
+                    .collect::<Vec<_>>();
+                let bulge = plummer(self, &mut rng, bulge_count, radius / 15.0, radius / 3.0);
+                let halo = plummer(self, &mut rng, halo_count, radius, radius * 2.0);
+                let disk_end = bodies.len();
+                bodies.extend(bulge);
+                bodies.extend(halo);
+                set_circular_velocities(&mut bodies, Vec2::ZERO, 0.0);
+                // The bulge and halo are pressure supported, not rotating
+                let dispersion = bodies[disk_end..]
+                    .iter()
+                    .map(|b| Vec2::new(b.v_x, b.v_y).length())
+                    .sum::<f32>()
+                    / (bodies.len() - disk_end).max(1) as f32;
+                for body in &mut bodies[disk_end..] {
+                    let v = rng.gaussian_vec() * dispersion * 0.5;
+                    body.v_x = v.x;
+                    body.v_y = v.y;
+                }
+                bodies
+            }
+            Generator::RandomField => random_field(self, &mut rng, count, radius),
+        }
+    }
+}
+
+/// Digits 1-5 run the disk, Plummer, ring, galaxy and random field
+/// generators at the cursor. 6/7 halve and double the count, 8/9 shrink and
+/// grow the radius, 0 picks a new seed and M cycles the mass distribution.
+/// Generated bodies become the selection.
+#[allow(clippy::too_many_arguments)]
+pub fn generator_input_system(
+    mut commands: Commands,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<GeneratorSettings>,
+    mut selection: ResMut<Selection>,
+    bodies: Query<(Entity, &Body)>,
+    frame: Res<ViewFrame>,
+    body_assets: Res<BodyAssets>,
+    mut recorder: HistoryRecorder,
+) {
+    if keyboard_input.just_pressed(KeyCode::Digit6) {
+        settings.count = (settings.count / 2).max(10);
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit7) {
+        settings.count = (settings.count * 2).min(20000);
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit8) {
+        settings.radius = (settings.radius / 1.25).max(20.0);
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit9) {
+        settings.radius = (settings.radius * 1.25).min(5000.0);
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit0) {
+        settings.seed += 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyM) {
+        settings.mass = settings.mass.next();
+    }
+
+    let generator = [
+        (KeyCode::Digit1, Generator::Disk),
+        (KeyCode::Digit2, Generator::Plummer),
+        (KeyCode::Digit3, Generator::Ring),
+        (KeyCode::Digit4, Generator::Galaxy),
+        (KeyCode::Digit5, Generator::RandomField),
+    ]
+    .into_iter()
+    .find(|(key, _)| keyboard_input.just_pressed(*key))
+    .map(|(_, generator)| generator);
+    let Some(generator) = generator else {
+        return;
+    };
+
+    let window = windows.single();
+    let (camera, camera_transform) = camera_query.single();
+    let Some(cursor) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+    let center = frame.transform.to_world(cursor);
+
+    // Rings orbit the selected body, or whatever pulls hardest at the cursor
+    let (center, velocity, central_mass) = if generator == Generator::Ring {
+        let primary = selection
+            .primary()
+            .and_then(|e| bodies.get(e).ok())
+            .or_else(|| orbit::strongest_pull(center, bodies.iter()));
+        let Some((_, primary)) = primary else {
+            warn!("A ring needs a body to orbit");
+            return;
+        };
+        let pos = Vec2::new(primary.x, primary.y);
+        (pos, Vec2::new(primary.v_x, primary.v_y), primary.mass)
+    } else {
+        (center, Vec2::ZERO, 0.0)
+    };
+
+    let generated = settings.generate(generator, central_mass);
+    info!(
+        "Generated {} bodies as a {} (seed {})",
+        generated.len(),
+        generator.label(),
+        settings.seed
+    );
+    selection.clear();
+    let mut spawned = Vec::with_capacity(generated.len());
+    for mut body in generated {
+        body.x += center.x;
+        body.y += center.y;
+        body.v_x += velocity.x;
+        body.v_y += velocity.y;
+        spawned.push(spawn_body(&mut commands, &body_assets, body).id());
+    }
+    // Freshly spawned, so no duplicates to check for
+    selection.entities.extend(&spawned);
+    recorder.spawned(spawned);
+    settings.last = Some(generator);
+}
+
+/// A direction uniformly distributed on the circle.
+fn rng_direction(rng: &mut Rng) -> Vec2 {
+    Vec2::from_angle(rng.f32() * TAU)
+}
+
+/// Bodies with a Plummer profile of scale radius `scale`, truncated at
+/// `max_radius`. Radii follow the 3D profile, laid out in the plane, with
+/// isotropic velocities from the Plummer distribution function.
+fn plummer(
+    settings: &GeneratorSettings,
+    rng: &mut Rng,
+    count: usize,
+    scale: f32,
+    max_radius: f32,
+) -> Vec<Body> {
+    let mut bodies = (0..count)
+        .map(|_| {
+            // Redraw radii past the cutoff rather than piling them up on it
+            let r = loop {
+                let u = rng.range(0.01, 1.0);
+                let r = scale / (u.powf(-2.0 / 3.0) - 1.0).sqrt();
+                if r <= max_radius {
+                    break r;
+                }
+            };
+            let pos = rng_direction(rng) * r;
+            settings.body(rng, pos)
+        })
+        .collect::<Vec<_>>();
+
+    let mass = bodies.iter().map(|b| b.mass).sum::<f32>();
+    for body in &mut bodies {
+        // Speed as a fraction of escape speed, by rejection sampling
+        let q = loop {
+            let (q, g) = (rng.f32(), rng.f32() * 0.1);
+            if g < q * q * (1.0 - q * q).powf(3.5) {
+                break q;
+            }
+        };
+        let r2 = body.x * body.x + body.y * body.y;
+        let escape = (2.0 * GRAVITY_CONST * mass / (r2 + scale * scale).sqrt()).sqrt();
+        let v = rng_direction(rng) * q * escape;
+        body.v_x = v.x;
+        body.v_y = v.y;
+    }
+    bodies
+}
+
+/// Scales velocities so kinetic energy is half the potential energy
+/// magnitude, measured directly on the bodies.
+fn virialize(bodies: &mut [Body]) {
+    let kinetic = bodies
+        .iter()
+        .map(|b| 0.5 * b.mass * (b.v_x * b.v_x + b.v_y * b.v_y))
+        .sum::<f32>();
+    let mut potential = 0.0;
+    for (i, a) in bodies.iter().enumerate() {
+        for b in &bodies[i + 1..] {
+            let distance = Vec2::new(a.x - b.x, a.y - b.y)
+                .length()
+                .max(a.size + b.size);
+            potential -= GRAVITY_CONST * a.mass * b.mass / distance;
+        }
+    }
+    if kinetic <= 0.0 {
+        return;
+    }
+    let scale = (-potential / (2.0 * kinetic)).sqrt();
+    for body in bodies {
+        body.v_x *= scale;
+        body.v_y *= scale;
+    }
+}
+
+/// Gives every body the counter-clockwise circular velocity around `center`
+/// for the mass of the bodies closer in plus `central_mass` there, treating
+/// the distribution as spherical.
+fn set_circular_velocities(bodies: &mut [Body], center: Vec2, central_mass: f32) {
+    let radius = |b: &Body| Vec2::new(b.x, b.y).distance(center);
+    let mut shells = bodies
+        .iter()
+        .map(|b| (radius(b), b.mass))
+        .collect::<Vec<_>>();
+    shells.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut enclosed = Vec::with_capacity(shells.len());
+    let mut total = central_mass;
+    for (r, mass) in &shells {
+        total += mass;
+        enclosed.push((*r, total));
+    }
+
+    for body in bodies {
+        let r = radius(body);
+        let index = enclosed.partition_point(|(shell, _)| *shell < r);
+        let mass = match index {
+            0 => central_mass,
+            i => enclosed[i - 1].1,
+        };
+        let offset = Vec2::new(body.x, body.y) - center;
+        let v = orbit::periapsis_velocity(offset, GRAVITY_CONST * mass, 0.0, false);
+        body.v_x = v.x;
+        body.v_y = v.y;
+    }
+}
+
+/// Cold bodies in a square of half-width `radius`, rejection sampled from
+/// `exp(delta)` where `delta` is a Gaussian random field with power spectrum
+/// `k^spectral_index`.
+fn random_field(
+    settings: &GeneratorSettings,
+    rng: &mut Rng,
+    count: usize,
+    radius: f32,
+) -> Vec<Body> {
+    let n = FIELD_GRID;
+    let mut planner = FftPlanner::new();
+    let forward = planner.plan_fft_forward(n);
+    let inverse = planner.plan_fft_inverse(n);
+
+    // Filter white noise in Fourier space
+    let mut grid = (0..n * n)
+        .map(|_| Complex::new(rng.gaussian(), 0.0))
+        .collect::<Vec<_>>();
+    fft_2d(&mut grid, n, &forward);
+    for j in 0..n {
+        for i in 0..n {
+            let wave = |i: usize| {
+                if i <= n / 2 {
+                    i as f32
+                } else {
+                    i as f32 - n as f32
+                }
+            };
+            let k = Vec2::new(wave(i), wave(j)).length();
+            let amplitude = if k == 0.0 {
+                0.0
+            } else {
+                k.powf(settings.spectral_index / 2.0)
+            };
+            grid[j * n + i] *= amplitude;
+        }
+    }
+    fft_2d(&mut grid, n, &inverse);
+
+    let field = grid.iter().map(|c| c.re).collect::<Vec<_>>();
+    let mean = field.iter().sum::<f32>() / field.len() as f32;
+    let deviation =
+        (field.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / field.len() as f32).sqrt();
+    let density = field
+        .iter()
+        .map(|v| ((v - mean) / deviation.max(f32::EPSILON) * FIELD_CONTRAST).exp())
+        .collect::<Vec<_>>();
+    let max_density = density.iter().copied().fold(0.0, f32::max);
+
+    let mut bodies = Vec::with_capacity(count);
+    while bodies.len() < count {
+        let cell = Vec2::new(rng.f32(), rng.f32());
+        let (i, j) = ((cell.x * n as f32) as usize, (cell.y * n as f32) as usize);
+        if rng.f32() * max_density < density[j.min(n - 1) * n + i.min(n - 1)] {
+            bodies.push(settings.body(rng, (cell * 2.0 - 1.0) * radius));
+        }
+    }
+    bodies
+}
+
+/// Small seeded generator (SplitMix64), so generated scenes are reproducible
+/// from their seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..1`.
+    fn f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn range(&mut self, lo: f32, hi: f32) -> f32 {
+        lo + (hi - lo) * self.f32()
+    }
+
+    /// Standard normal, by Box-Muller.
+    fn gaussian(&mut self) -> f32 {
+        self.gaussian_vec().x
+    }
+
+    /// Two independent standard normals.
+    fn gaussian_vec(&mut self) -> Vec2 {
+        let r = (-2.0 * (1.0 - self.f32()).ln()).sqrt();
+        Vec2::from_angle(self.f32() * TAU) * r
+    }
+}
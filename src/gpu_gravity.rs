@@ -0,0 +1,254 @@
+use std::sync::mpsc;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+
+use crate::GRAVITY_CONST;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+/// State of the compute-shader gravity path. The kernel is built on first use
+/// from Bevy's render device; when no device is available the CPU pairwise
+/// sum is used instead.
+#[derive(Resource, Default)]
+pub struct GpuGravity {
+    kernel: Option<GpuKernel>,
+    /// Set once the GPU path has had to fall back to the CPU.
+    pub fallback: bool,
+}
+
+/// Bundles the GPU state with the render device and queue it runs on.
+#[derive(SystemParam)]
+pub struct GpuGravityParam<'w> {
+    state: ResMut<'w, GpuGravity>,
+    device: Option<Res<'w, RenderDevice>>,
+    queue: Option<Res<'w, RenderQueue>>,
+}
+
+impl GpuGravityParam<'_> {
+    /// Accelerations from the GPU for exactly these bodies. Returns `None` if
+    /// there is no render device to run on or the result couldn't be read
+    /// back.
+    pub fn accelerations(&mut self, bodies: &[(Vec2, f32)]) -> Option<Vec<Vec2>> {
+        let (Some(device), Some(queue)) = (&self.device, &self.queue) else {
+            if !self.state.fallback {
+                warn!("No render device for GPU gravity, using the CPU path");
+                self.state.fallback = true;
+            }
+            return None;
+        };
+        let device = device.wgpu_device();
+        let kernel = self
+            .state
+            .kernel
+            .get_or_insert_with(|| GpuKernel::new(device));
+        kernel.accelerations(device, queue, bodies)
+    }
+}
+
+/// All-pairs force kernel with body state held in storage buffers. Each
+/// call waits for its own dispatch, so a result is never applied to a
+/// different set of bodies or positions than it was computed for.
+pub struct GpuKernel {
+    pipeline: wgpu::ComputePipeline,
+    params: wgpu::Buffer,
+    buffers: Option<BodyBuffers>,
+}
+
+struct BodyBuffers {
+    capacity: usize,
+    bodies: wgpu::Buffer,
+    accelerations: wgpu::Buffer,
+    readback: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl GpuKernel {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("gravity_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../assets/shaders/gravity.wgsl").into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("gravity_pipeline"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+        });
+        let params = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gravity_params"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        GpuKernel {
+            pipeline,
+            params,
+            buffers: None,
+        }
+    }
+
+    /// Runs the kernel for `bodies` and waits for its result. Returns `None`
+    /// if the result couldn't be read back.
+    pub fn accelerations(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bodies: &[(Vec2, f32)],
+    ) -> Option<Vec<Vec2>> {
+        let count = bodies.len();
+        if count == 0 {
+            return Some(Vec::new());
+        }
+        let buffers = match self.buffers.take() {
+            Some(buffers) if buffers.capacity >= count => buffers,
+            _ => self.create_buffers(device, count.next_power_of_two()),
+        };
+
+        let data: Vec<Vec4> = bodies
+            .iter()
+            .map(|&(pos, mass)| Vec4::new(pos.x, pos.y, mass, 0.0))
+            .collect();
+        queue.write_buffer(&buffers.bodies, 0, bytemuck::cast_slice(&data));
+        let params = [
+            count as u32,
+            GRAVITY_CONST.to_bits(),
+            0.0001f32.to_bits(),
+            0,
+        ];
+        queue.write_buffer(&self.params, 0, bytemuck::cast_slice(&params));
+
+        let size = (count * std::mem::size_of::<Vec2>()) as wgpu::BufferAddress;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("gravity_encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("gravity_pass"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &buffers.bind_group, &[]);
+            pass.dispatch_workgroups((count as u32).div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&buffers.accelerations, 0, &buffers.readback, 0, size);
+        let submission = queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = mpsc::channel();
+        let slice = buffers.readback.slice(..size);
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result.is_ok());
+        });
+        device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
+
+        let result = if receiver.try_recv() == Ok(true) {
+            Some(bytemuck::cast_slice::<u8, Vec2>(&slice.get_mapped_range()).to_vec())
+        } else {
+            error!("Failed to read back GPU gravity results");
+            None
+        };
+        // Also cancels a map that never finished, so the next call can map again
+        buffers.readback.unmap();
+        self.buffers = Some(buffers);
+        result
+    }
+
+    fn create_buffers(&self, device: &wgpu::Device, capacity: usize) -> BodyBuffers {
+        let body_size = (capacity * std::mem::size_of::<Vec4>()) as wgpu::BufferAddress;
+        let acc_size = (capacity * std::mem::size_of::<Vec2>()) as wgpu::BufferAddress;
+
+        let bodies = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gravity_bodies"),
+            size: body_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let accelerations = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gravity_accelerations"),
+            size: acc_size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gravity_readback"),
+            size: acc_size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gravity_bind_group"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: bodies.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: accelerations.as_entire_binding(),
+                },
+            ],
+        });
+
+        BodyBuffers {
+            capacity,
+            bodies,
+            accelerations,
+            readback,
+            bind_group,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pairwise_accelerations;
+
+    fn sample_bodies() -> Vec<(Vec2, f32)> {
+        (0..300)
+            .map(|i| {
+                let angle = i as f32 * 2.399;
+                let radius = 10.0 + i as f32 * 3.0;
+                let pos = Vec2::new(angle.cos(), angle.sin()) * radius;
+                (pos, 1.0 + (i % 7) as f32 * 20.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn gpu_matches_cpu() {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let Some(adapter) = bevy::tasks::block_on(
+            instance.request_adapter(&wgpu::RequestAdapterOptions::default()),
+        ) else {
+            // Nothing to compare against without a GPU
+            return;
+        };
+        let (device, queue) =
+            bevy::tasks::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None))
+                .expect("failed to create GPU device");
+
+        let bodies = sample_bodies();
+        let cpu = pairwise_accelerations(&bodies);
+        let mut kernel = GpuKernel::new(&device);
+        let gpu = kernel
+            .accelerations(&device, &queue, &bodies)
+            .expect("failed to read back GPU result");
+
+        assert_eq!(cpu.len(), gpu.len());
+        for (cpu, gpu) in cpu.iter().zip(&gpu) {
+            let tolerance = cpu.length() * 1e-3 + 1e-9;
+            assert!(
+                (*cpu - *gpu).length() <= tolerance,
+                "cpu {cpu:?} gpu {gpu:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use bevy::prelude::*;
+
+use crate::TIME_SCALE;
+use crate::body::Body;
+use crate::clipboard::Clipboard;
+use crate::diagnostics::ConservationDiagnostics;
+use crate::history::HistoryRecorder;
+use crate::selection;
+use crate::view_frame::ViewFrame;
+
+/// How quickly the throw velocity follows the cursor, per second. Lower
+/// values average out jitter at the end of a drag.
+const THROW_SMOOTHING: f32 = 20.0;
+/// How far in pixels the cursor has to move before a press on a body
+/// becomes a drag. Shorter presses are clicks that only select it.
+const DRAG_THRESHOLD: f32 = 4.0;
+
+/// Marks a body held by the mouse. Held bodies are moved by hand instead of
+/// integrated, but still pull on everything else.
+#[derive(Component)]
+pub struct Held;
+
+/// The body being dragged, if any.
+#[derive(Resource, Default)]
+pub struct Grab {
+    entity: Option<Entity>,
+    /// Where the button went down, in view coordinates so a moving frame
+    /// doesn't count as a drag.
+    start: Vec2,
+    /// Whether the cursor has moved far enough to take over the body.
+    dragging: bool,
+    /// From the cursor to the body center, in world coordinates.
+    offset: Vec2,
+    /// Body position on the previous frame, in world coordinates.
+    last_pos: Vec2,
+    /// Smoothed drag velocity in simulation units, given to the body on
+    /// release.
+    velocity: Vec2,
+    /// The body as it was when grabbed, recorded for undo if the drag
+    /// changed it.
+    before: Option<Body>,
+}
+
+/// Left-dragging a body moves it; releasing throws it with the velocity of
+/// the drag. Clicks that don't move past `DRAG_THRESHOLD` leave the body
+/// alone. Shift-clicks are left to the selection and clicks placing a
+/// paste to the clipboard.
+#[allow(clippy::too_many_arguments)]
+pub fn grab_system(
+    mut commands: Commands,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    camera_transform_query: Query<&Transform, With<Camera2d>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut grab: ResMut<Grab>,
+    mut bodies: Query<(Entity, &mut Body)>,
+    frame: Res<ViewFrame>,
+    clipboard: Res<Clipboard>,
+    mut recorder: HistoryRecorder,
+    mut diagnostics: ResMut<ConservationDiagnostics>,
+    time: Res<Time>,
+) {
+    let window = windows.single();
+    let (camera, camera_transform) = camera_query.single();
+    let view_cursor = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor));
+    let cursor = view_cursor.map(|view| frame.transform.to_world(view));
+
+    let pressed = mouse_button_input.just_pressed(MouseButton::Left)
+        && !keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+        && !clipboard.pasting();
+    let scale = camera_transform_query.single().scale.x;
+    if let (Some(cursor), Some(view)) = (cursor.filter(|_| pressed), view_cursor) {
+        let picked = selection::pick(bodies.iter(), cursor, 6.0 * scale);
+        if let Some((entity, body)) = picked.and_then(|e| bodies.get(e).ok()) {
+            *grab = Grab {
+                entity: Some(entity),
+                start: view,
+                before: Some(*body),
+                ..default()
+            };
+        }
+    }
+
+    let Some(entity) = grab.entity else {
+        return;
+    };
+    let Ok((_, mut body)) = bodies.get_mut(entity) else {
+        // Deleted while held
+        grab.entity = None;
+        return;
+    };
+
+    let held = mouse_button_input.pressed(MouseButton::Left);
+    if held
+        && !grab.dragging
+        && let (Some(cursor), Some(view)) = (cursor, view_cursor)
+        && view.distance(grab.start) > DRAG_THRESHOLD * scale
+    {
+        // Take over from where the body is now so it doesn't jump
+        let pos = Vec2::new(body.x, body.y);
+        grab.dragging = true;
+        grab.offset = pos - cursor;
+        grab.last_pos = pos;
+        commands.entity(entity).insert(Held);
+    }
+    if !grab.dragging {
+        // A click, or a press that hasn't moved yet, is only a selection
+        if !held {
+            grab.entity = None;
+        }
+        return;
+    }
+
+    if held {
+        let pos = cursor.map_or(grab.last_pos, |cursor| cursor + grab.offset);
+        let mult = time.delta_seconds() * TIME_SCALE;
+        if mult > 0.0 {
+            let velocity = (pos - grab.last_pos) / mult;
+            let t = 1.0 - (-THROW_SMOOTHING * time.delta_seconds()).exp();
+            grab.velocity = grab.velocity.lerp(velocity, t);
+        }
+        grab.last_pos = pos;
+
+        body.past_x = body.x;
+        body.past_y = body.y;
+        body.x = pos.x;
+        body.y = pos.y;
+        body.v_x = grab.velocity.x;
+        body.v_y = grab.velocity.y;
+        body.a_x = 0.0;
+        body.a_y = 0.0;
+        body.past_a_x = 0.0;
+        body.past_a_y = 0.0;
+    } else {
+        // Thrown bodies carry energy the system didn't have before
+        body.v_x = grab.velocity.x;
+        body.v_y = grab.velocity.y;
+        commands.entity(entity).remove::<Held>();
+        grab.entity = None;
+        if let Some(before) = grab.before.take() {
+            recorder.edited(vec![(entity, before)]);
+        }
+        diagnostics.rebaseline();
+    }
+}
@@ -0,0 +1,228 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::body::{Body, BodyAssets, spawn_body};
+use crate::camera::{CameraController, CameraFollow};
+use crate::diagnostics::ConservationDiagnostics;
+use crate::orbit::OrbitPrimary;
+use crate::selection::Selection;
+use crate::sph::GasParticle;
+use crate::trail::{TrailFrame, TrailSettings};
+use crate::view_frame::{FrameKind, ViewFrame};
+
+/// Oldest entries are dropped beyond this many undo steps.
+const MAX_HISTORY: usize = 64;
+
+/// One editor action, holding what it takes to revert it. Only the bodies
+/// the action touched are stored, so reverting leaves the rest of the scene
+/// running as it is.
+enum EditCommand {
+    /// Bodies were spawned.
+    Spawned(Vec<Entity>),
+    /// Bodies were despawned, with their state just before.
+    Despawned(Vec<(Entity, Body, Option<GasParticle>)>),
+    /// Bodies were edited in place, with their state just before.
+    Edited(Vec<(Entity, Body)>),
+}
+
+impl EditCommand {
+    /// Reverts the action and returns the command that reverts that in turn,
+    /// along with where the bodies it despawned or respawned went: `None`
+    /// for despawned ones and the new entity for respawned ones.
+    fn revert(
+        self,
+        commands: &mut Commands,
+        bodies: &mut Query<(Entity, &mut Body, Option<&GasParticle>)>,
+        body_assets: &BodyAssets,
+    ) -> (EditCommand, HashMap<Entity, Option<Entity>>) {
+        let mut moved = HashMap::new();
+        let reverted = match self {
+            EditCommand::Spawned(entities) => {
+                let removed = bodies
+                    .iter_many(&entities)
+                    .map(|(entity, body, gas)| (entity, *body, gas.copied()))
+                    .collect::<Vec<_>>();
+                for (entity, _, _) in &removed {
+                    commands.entity(*entity).despawn();
+                    moved.insert(*entity, None);
+                }
+                EditCommand::Despawned(removed)
+            }
+            EditCommand::Despawned(removed) => {
+                // Entities can't be brought back, so these get new ones
+                let spawned = removed
+                    .into_iter()
+                    .map(|(old, body, gas)| {
+                        let mut entity = spawn_body(commands, body_assets, body);
+                        if let Some(gas) = gas {
+                            entity.insert(gas);
+                        }
+                        moved.insert(old, Some(entity.id()));
+                        entity.id()
+                    })
+                    .collect();
+                EditCommand::Spawned(spawned)
+            }
+            EditCommand::Edited(edits) => {
+                let mut reverted = Vec::with_capacity(edits.len());
+                for (entity, before) in edits {
+                    if let Ok((_, mut body, _)) = bodies.get_mut(entity) {
+                        reverted.push((entity, *body));
+                        *body = before;
+                    }
+                }
+                EditCommand::Edited(reverted)
+            }
+        };
+        (reverted, moved)
+    }
+
+    fn entities_mut(&mut self) -> Vec<&mut Entity> {
+        match self {
+            EditCommand::Spawned(entities) => entities.iter_mut().collect(),
+            EditCommand::Despawned(bodies) => bodies.iter_mut().map(|(e, _, _)| e).collect(),
+            EditCommand::Edited(edits) => edits.iter_mut().map(|(e, _)| e).collect(),
+        }
+    }
+}
+
+/// Undo and redo stacks of editor actions. The simulation keeps running
+/// between edits; undoing an action only reverts the bodies it touched.
+#[derive(Resource, Default)]
+pub struct History {
+    undo: Vec<EditCommand>,
+    redo: Vec<EditCommand>,
+}
+
+impl History {
+    /// Points entries at the entities respawned bodies came back as.
+    fn retarget(&mut self, moved: &HashMap<Entity, Option<Entity>>) {
+        for command in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            for entity in command.entities_mut() {
+                if let Some(Some(new)) = moved.get(entity) {
+                    *entity = *new;
+                }
+            }
+        }
+    }
+}
+
+/// Gives editing systems a way to record what they change.
+#[derive(SystemParam)]
+pub struct HistoryRecorder<'w, 's> {
+    history: ResMut<'w, History>,
+    gas: Query<'w, 's, &'static GasParticle>,
+}
+
+impl HistoryRecorder<'_, '_> {
+    /// Records that `entities` were just spawned.
+    pub fn spawned(&mut self, entities: Vec<Entity>) {
+        if !entities.is_empty() {
+            self.push(EditCommand::Spawned(entities));
+        }
+    }
+
+    /// Records `bodies` as they are just before being despawned. Call
+    /// before despawning them.
+    pub fn despawned<'a>(&mut self, bodies: impl Iterator<Item = (Entity, &'a Body)>) {
+        let removed = bodies
+            .map(|(entity, body)| (entity, *body, self.gas.get(entity).ok().copied()))
+            .collect::<Vec<_>>();
+        if !removed.is_empty() {
+            self.push(EditCommand::Despawned(removed));
+        }
+    }
+
+    /// Records an edit of bodies from the state they had before it.
+    pub fn edited(&mut self, before: Vec<(Entity, Body)>) {
+        if !before.is_empty() {
+            self.push(EditCommand::Edited(before));
+        }
+    }
+
+    fn push(&mut self, command: EditCommand) {
+        let history = &mut self.history;
+        history.undo.push(command);
+        if history.undo.len() > MAX_HISTORY {
+            history.undo.remove(0);
+        }
+        history.redo.clear();
+    }
+}
+
+/// Everything outside the history that refers to bodies by entity.
+#[derive(SystemParam)]
+pub struct EntityReferences<'w> {
+    selection: ResMut<'w, Selection>,
+    orbit_primary: ResMut<'w, OrbitPrimary>,
+    camera_controller: ResMut<'w, CameraController>,
+    view_frame: ResMut<'w, ViewFrame>,
+    trails: ResMut<'w, TrailSettings>,
+}
+
+impl EntityReferences<'_> {
+    /// Follows bodies that moved to a new entity and drops references to
+    /// ones that are gone.
+    fn retarget(&mut self, moved: &HashMap<Entity, Option<Entity>>) {
+        let map = |entity: Entity| moved.get(&entity).copied().unwrap_or(Some(entity));
+
+        let selected = self.selection.entities.iter().filter_map(|e| map(*e));
+        self.selection.entities = selected.collect();
+        self.orbit_primary.0 = self.orbit_primary.0.and_then(map);
+        if let CameraFollow::Body(entity) = self.camera_controller.follow {
+            self.camera_controller.follow =
+                map(entity).map_or(CameraFollow::Free, CameraFollow::Body);
+        }
+        self.view_frame.kind = match self.view_frame.kind {
+            FrameKind::Body(entity) => map(entity).map_or(FrameKind::World, FrameKind::Body),
+            FrameKind::CoRotating(a, b) => match (map(a), map(b)) {
+                (Some(a), Some(b)) => FrameKind::CoRotating(a, b),
+                _ => FrameKind::World,
+            },
+            kind => kind,
+        };
+        if let TrailFrame::Body(entity) = self.trails.frame {
+            self.trails.frame = map(entity).map_or(TrailFrame::View, TrailFrame::Body);
+        }
+    }
+}
+
+/// Ctrl+Z undoes the last editor action; Ctrl+Y or Ctrl+Shift+Z redoes it.
+pub fn history_input_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut history: ResMut<History>,
+    mut bodies: Query<(Entity, &mut Body, Option<&GasParticle>)>,
+    body_assets: Res<BodyAssets>,
+    mut references: EntityReferences,
+    mut diagnostics: ResMut<ConservationDiagnostics>,
+) {
+    if !keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let undo = keyboard_input.just_pressed(KeyCode::KeyZ) && !shift;
+    let redo = keyboard_input.just_pressed(KeyCode::KeyY)
+        || (keyboard_input.just_pressed(KeyCode::KeyZ) && shift);
+    if !undo && !redo {
+        return;
+    }
+
+    let history = &mut *history;
+    let (from, to) = if undo {
+        (&mut history.undo, &mut history.redo)
+    } else {
+        (&mut history.redo, &mut history.undo)
+    };
+    let Some(command) = from.pop() else {
+        return;
+    };
+    let (reverted, moved) = command.revert(&mut commands, &mut bodies, &body_assets);
+    to.push(reverted);
+
+    history.retarget(&moved);
+    references.retarget(&moved);
+    // Edits are not physics; measure drift from the restored state
+    diagnostics.rebaseline();
+}
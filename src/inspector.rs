@@ -0,0 +1,340 @@
+use bevy::prelude::*;
+
+use crate::TIME_SCALE;
+use crate::body::Body;
+use crate::diagnostics::ConservationDiagnostics;
+use crate::history::HistoryRecorder;
+use crate::orbit::{OrbitPrimary, OrbitalElements};
+use crate::selection::{CenterOfMass, Selection};
+
+/// A property of the selected body that can be edited from the inspector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InspectorField {
+    #[default]
+    PositionX,
+    PositionY,
+    VelocityX,
+    VelocityY,
+    Mass,
+    Size,
+    Density,
+    Red,
+    Green,
+    Blue,
+}
+
+impl InspectorField {
+    pub const ALL: [InspectorField; 10] = [
+        InspectorField::PositionX,
+        InspectorField::PositionY,
+        InspectorField::VelocityX,
+        InspectorField::VelocityY,
+        InspectorField::Mass,
+        InspectorField::Size,
+        InspectorField::Density,
+        InspectorField::Red,
+        InspectorField::Green,
+        InspectorField::Blue,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            InspectorField::PositionX => "POS X",
+            InspectorField::PositionY => "POS Y",
+            InspectorField::VelocityX => "VEL X",
+            InspectorField::VelocityY => "VEL Y",
+            InspectorField::Mass => "MASS",
+            InspectorField::Size => "SIZE",
+            InspectorField::Density => "DENSITY",
+            InspectorField::Red => "RED",
+            InspectorField::Green => "GREEN",
+            InspectorField::Blue => "BLUE",
+        }
+    }
+
+    fn value(self, body: &Body) -> f32 {
+        match self {
+            InspectorField::PositionX => body.x,
+            InspectorField::PositionY => body.y,
+            InspectorField::VelocityX => body.v_x,
+            InspectorField::VelocityY => body.v_y,
+            InspectorField::Mass => body.mass,
+            InspectorField::Size => body.size,
+            InspectorField::Density => body.density,
+            InspectorField::Red => body.color.r(),
+            InspectorField::Green => body.color.g(),
+            InspectorField::Blue => body.color.b(),
+        }
+    }
+
+    fn is_color(self) -> bool {
+        matches!(
+            self,
+            InspectorField::Red | InspectorField::Green | InspectorField::Blue
+        )
+    }
+
+    fn format(self, body: &Body) -> String {
+        match self {
+            InspectorField::Mass => format!("{:.3E}", self.value(body)),
+            InspectorField::VelocityX | InspectorField::VelocityY => {
+                format!("{:.3}", self.value(body))
+            }
+            InspectorField::Red | InspectorField::Green | InspectorField::Blue => {
+                format!("{:.2}", self.value(body))
+            }
+            _ => format!("{:.1}", self.value(body)),
+        }
+    }
+
+    /// Nudges the field one step up (`direction` 1) or down (-1). Coarse
+    /// steps are ten times larger. Mass, size and density stay consistent:
+    /// editing mass changes density, editing size or density changes mass.
+    pub fn adjust(self, body: &mut Body, direction: f32, coarse: bool) {
+        let scale = if coarse { 10.0 } else { 1.0 };
+        let step = direction * scale;
+        match self {
+            InspectorField::PositionX => body.x += step,
+            InspectorField::PositionY => body.y += step,
+            InspectorField::VelocityX => body.v_x += 0.01 * step,
+            InspectorField::VelocityY => body.v_y += 0.01 * step,
+            InspectorField::Mass => {
+                body.mass *= 1.01f32.powf(step);
+                body.density = body.mass / body.volume();
+            }
+            InspectorField::Size => {
+                body.size = (body.size + 0.1 * step).max(1.0);
+                body.mass = body.volume() * body.density;
+            }
+            InspectorField::Density => {
+                body.density *= 1.01f32.powf(step);
+                body.mass = body.volume() * body.density;
+            }
+            InspectorField::Red => {
+                let r = (body.color.r() + 0.01 * step).clamp(0.0, 1.0);
+                body.color.set_r(r);
+            }
+            InspectorField::Green => {
+                let g = (body.color.g() + 0.01 * step).clamp(0.0, 1.0);
+                body.color.set_g(g);
+            }
+            InspectorField::Blue => {
+                let b = (body.color.b() + 0.01 * step).clamp(0.0, 1.0);
+                body.color.set_b(b);
+            }
+        }
+    }
+}
+
+/// Which inspector field the arrow keys edit.
+#[derive(Resource, Default)]
+pub struct Inspector {
+    pub field: InspectorField,
+}
+
+#[derive(Component)]
+pub struct InspectorText;
+
+pub fn inspector_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/start.ttf");
+
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font,
+                    font_size: 12.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(120.0),
+                left: Val::Px(10.0),
+                ..default()
+            }),
+        )
+        .insert(InspectorText);
+}
+
+/// Shows the editable properties and live orbital elements of the selected
+/// body, and a summary of the group when several are selected.
+pub fn inspector_update_system(
+    mut query: Query<&mut Text, With<InspectorText>>,
+    bodies: Query<(Entity, &Body)>,
+    selection: Res<Selection>,
+    orbit_primary: Res<OrbitPrimary>,
+    inspector: Res<Inspector>,
+) {
+    let text = match selection.primary() {
+        Some(selected) => {
+            let mut text = String::new();
+            if selection.entities.len() > 1 {
+                let group = selection
+                    .entities
+                    .iter()
+                    .filter_map(|e| bodies.get(*e).ok().map(|(_, body)| body));
+                if let Some(center) = CenterOfMass::of(group) {
+                    text.push_str(&format!(
+                        "GROUP: {} BODIES\nTOTAL MASS: {:.3E}\nCOM: {:.1}, {:.1}\nCOM VEL: {:.3}, {:.3}\n\n",
+                        selection.entities.len(),
+                        center.mass,
+                        center.pos.x,
+                        center.pos.y,
+                        center.vel.x,
+                        center.vel.y,
+                    ));
+                }
+            }
+            if let Ok((_, body)) = bodies.get(selected) {
+                for field in InspectorField::ALL {
+                    let marker = if field == inspector.field { ">" } else { " " };
+                    text.push_str(&format!(
+                        "{}{}: {}\n",
+                        marker,
+                        field.label(),
+                        field.format(body)
+                    ));
+                }
+                text.push('\n');
+            }
+            text.push_str(&orbit_text(selected, &bodies, &orbit_primary));
+            text
+        }
+        None => String::new(),
+    };
+    for mut inspector_text in query.iter_mut() {
+        inspector_text.sections[0].value = text.clone();
+    }
+}
+
+fn orbit_text(
+    selected: Entity,
+    bodies: &Query<(Entity, &Body)>,
+    orbit_primary: &OrbitPrimary,
+) -> String {
+    let mut text = format!("SELECTED: {:?}\n", selected);
+
+    let Some(primary) = orbit_primary.resolve(selected, bodies.iter()) else {
+        text.push_str("PRIMARY: NONE");
+        return text;
+    };
+    let pinned = orbit_primary.0 == Some(primary);
+    text.push_str(&format!(
+        "PRIMARY: {:?} ({})\n",
+        primary,
+        if pinned { "PINNED" } else { "DOMINANT" }
+    ));
+
+    let (Ok((_, body)), Ok((_, primary_body))) = (bodies.get(selected), bodies.get(primary)) else {
+        return text;
+    };
+    let Some(elements) = OrbitalElements::from_bodies(body, primary_body) else {
+        return text;
+    };
+
+    let optional = |value: Option<f32>| match value {
+        Some(value) => format!("{:.1}", value),
+        None => "UNBOUND".to_string(),
+    };
+    // Simulation time units, and how long that takes in real seconds
+    let period = match elements.period {
+        Some(period) => format!("{:.1} SIM ({:.1} S)", period, period / TIME_SCALE),
+        None => "UNBOUND".to_string(),
+    };
+    text.push_str(&format!(
+        "A: {:.1}\nE: {:.4}\nARG PERI: {:.1} DEG{}\nPERIOD: {}\nPERI: {:.1}\nAPO: {}",
+        elements.semi_major_axis,
+        elements.eccentricity,
+        elements.argument_of_periapsis.to_degrees(),
+        if elements.retrograde { " (RETRO)" } else { "" },
+        period,
+        elements.periapsis,
+        optional(elements.apoapsis),
+    ));
+    text
+}
+
+/// `Y` pins the selected body as the primary for orbital elements, or unpins
+/// the current one.
+pub fn orbit_primary_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    selection: Res<Selection>,
+    mut orbit_primary: ResMut<OrbitPrimary>,
+) {
+    // Ctrl+Y is redo
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    if keyboard_input.just_pressed(KeyCode::KeyY) && !ctrl {
+        orbit_primary.0 = match orbit_primary.0 {
+            Some(_) => None,
+            None => selection.primary(),
+        };
+    }
+}
+
+/// Up/Down picks an inspector field and Left/Right adjusts it on every
+/// selected body, with Shift for coarse steps. Works while paused too. Color
+/// edits set the whole group to the primary body's new color. Holding an
+/// arrow key is one undo step, recorded when it is released.
+pub fn inspector_edit_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    selection: Res<Selection>,
+    mut inspector: ResMut<Inspector>,
+    mut bodies: Query<(Entity, &mut Body)>,
+    mut recorder: HistoryRecorder,
+    mut diagnostics: ResMut<ConservationDiagnostics>,
+    mut before: Local<Vec<(Entity, Body)>>,
+) {
+    if !keyboard_input.any_pressed([KeyCode::ArrowLeft, KeyCode::ArrowRight]) {
+        recorder.edited(std::mem::take(&mut *before));
+    }
+    // Ctrl+arrows belong to other editing commands
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    let fields = InspectorField::ALL;
+    let index = fields
+        .iter()
+        .position(|f| *f == inspector.field)
+        .unwrap_or(0);
+    if keyboard_input.just_pressed(KeyCode::ArrowUp) {
+        inspector.field = fields[(index + fields.len() - 1) % fields.len()];
+    }
+    if keyboard_input.just_pressed(KeyCode::ArrowDown) {
+        inspector.field = fields[(index + 1) % fields.len()];
+    }
+
+    let direction = match (
+        keyboard_input.pressed(KeyCode::ArrowRight),
+        keyboard_input.pressed(KeyCode::ArrowLeft),
+    ) {
+        (true, false) => 1.0,
+        (false, true) => -1.0,
+        _ => return,
+    };
+    let Some(primary) = selection.primary() else {
+        return;
+    };
+    if before.is_empty() {
+        let selected = bodies.iter_many(&selection.entities);
+        *before = selected.map(|(entity, body)| (entity, *body)).collect();
+    }
+    let coarse = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let mut iter = bodies.iter_many_mut(&selection.entities);
+    while let Some((_, mut body)) = iter.fetch_next() {
+        inspector.field.adjust(&mut body, direction, coarse);
+    }
+    if inspector.field.is_color() {
+        let Ok(color) = bodies.get(primary).map(|(_, body)| body.color) else {
+            return;
+        };
+        let mut iter = bodies.iter_many_mut(&selection.entities);
+        while let Some((_, mut body)) = iter.fetch_next() {
+            body.color = color.with_a(body.color.a());
+        }
+    }
+    // Edits are not physics; measure drift from the edited state
+    diagnostics.rebaseline();
+}
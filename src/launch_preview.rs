@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+
+use crate::SelectedBodyState;
+use crate::body::Body;
+use crate::view_frame::ViewFrame;
+
+/// Speed readout shown next to the cursor while dragging out a new body.
+#[derive(Component)]
+pub struct LaunchReadout;
+
+pub fn launch_readout_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/start.ttf");
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font,
+                font_size: 10.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            ..default()
+        }),
+        LaunchReadout,
+    ));
+}
+
+/// While dragging out a new body, draws a ghost of it at the start point,
+/// the drag line and the velocity it will launch with, and shows the speed
+/// next to the cursor. Alt scales the drag down for fine control.
+#[allow(clippy::too_many_arguments)]
+pub fn launch_preview_system(
+    mut gizmos: Gizmos,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    selected_body_state: Res<SelectedBodyState>,
+    bodies: Query<(Entity, &Body)>,
+    frame: Res<ViewFrame>,
+    mut readout: Query<(&mut Text, &mut Style, &mut Visibility), With<LaunchReadout>>,
+) {
+    let (mut text, mut style, mut visibility) = readout.single_mut();
+    let window = windows.single();
+    let (camera, camera_transform) = camera_query.single();
+    let screen_cursor = window.cursor_position();
+    let cursor = screen_cursor
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+        .filter(|_| selected_body_state.pos_selected);
+    let (Some(screen_cursor), Some(cursor)) = (screen_cursor, cursor) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    let fine = keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    let (_, velocity) = selected_body_state.launch(cursor, fine, frame.transform, bodies.iter());
+    let start = selected_body_state.selected_pos;
+    let radius = if selected_body_state.spawn_gas {
+        // Matches the cloud radius used when spawning
+        selected_body_state.selected_size * 4.0
+    } else {
+        selected_body_state.selected_size
+    };
+
+    gizmos.circle_2d(start, radius, Color::rgba(1.0, 1.0, 1.0, 0.4));
+    gizmos.line_2d(start, cursor, Color::rgba(1.0, 1.0, 1.0, 0.3));
+    // Drawn at the drag scale, so in drag mode it ends at the cursor
+    let tip =
+        start + frame.transform.vector_to_view(velocity) * SelectedBodyState::drag_scale(fine);
+    gizmos.arrow_2d(start, tip, Color::rgb(0.4, 1.0, 0.4));
+
+    *visibility = Visibility::Inherited;
+    style.left = Val::Px(screen_cursor.x + 16.0);
+    style.top = Val::Px(screen_cursor.y + 16.0);
+    text.sections[0].value = format!(
+        "V: {:.3}{}",
+        velocity.length(),
+        if fine { " (FINE)" } else { "" }
+    );
+}
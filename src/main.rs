@@ -1,78 +1,208 @@
-use bevy::input::mouse::MouseWheel;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
-use bevy::sprite::MaterialMesh2dBundle;
 
 mod body;
-use body::Body;
+mod body_color;
+mod camera;
+mod clipboard;
+mod colormap;
+mod diagnostics;
+mod fmm;
+mod generators;
+mod gpu_gravity;
+mod grab;
+mod history;
+mod inspector;
+mod launch_preview;
+mod orbit;
+mod particle_mesh;
+mod plot;
+mod potential_field;
+mod selection;
+mod spacetime_grid;
+mod sph;
+mod trail;
+mod trajectory;
+mod vector_overlay;
+mod view_frame;
+use body::{Body, BodyAssets, spawn_body};
+use body_color::BodyColoring;
+use camera::CameraController;
+use clipboard::Clipboard;
+use diagnostics::ConservationDiagnostics;
+use fmm::Fmm;
+use generators::GeneratorSettings;
+use gpu_gravity::{GpuGravity, GpuGravityParam};
+use grab::{Grab, Held};
+use history::{History, HistoryRecorder};
+use inspector::Inspector;
+use orbit::OrbitPrimary;
+use particle_mesh::ParticleMesh;
+use plot::PlotPanel;
+use potential_field::PotentialField;
+use selection::{CenterOfMass, Selection};
+use spacetime_grid::SpacetimeGrid;
+use sph::{GasCloud, GasParticle, SphSettings};
+use trail::{TrailGizmos, TrailSettings};
+use trajectory::TrajectoryPreview;
+use vector_overlay::VectorOverlay;
+use view_frame::{FrameTransform, ViewFrame};
 
 const GRAVITY_CONST: f32 = 0.0005;
+/// Simulation time units per real second.
+const TIME_SCALE: f32 = 400.0;
 
 fn main() {
     App::new()
         .add_plugins(DefaultPlugins)
         .register_type::<Body>()
+        .register_type::<GasParticle>()
+        .init_resource::<BodyAssets>()
         .init_resource::<SelectedBodyState>()
         .init_resource::<ElasticCollisionsEnabled>() // Initialize the resource
-        .add_systems(Startup, (setup, hud_setup))
+        .init_resource::<GravitySolver>()
+        .init_resource::<ParticleMesh>()
+        .init_resource::<Fmm>()
+        .init_resource::<GpuGravity>()
+        .init_resource::<SphSettings>()
+        .init_resource::<ForceErrorComparison>()
+        .init_resource::<ConservationDiagnostics>()
+        .init_resource::<Selection>()
+        .init_resource::<PlotPanel>()
+        .init_resource::<OrbitPrimary>()
+        .init_resource::<TrajectoryPreview>()
+        .init_resource::<TrailSettings>()
+        .init_gizmo_group::<TrailGizmos>()
+        .init_resource::<ViewFrame>()
+        .init_resource::<VectorOverlay>()
+        .init_resource::<PotentialField>()
+        .init_resource::<SpacetimeGrid>()
+        .init_resource::<BodyColoring>()
+        .init_resource::<CameraController>()
+        .init_resource::<SimulationPaused>()
+        .init_resource::<Inspector>()
+        .init_resource::<Grab>()
+        .init_resource::<Clipboard>()
+        .init_resource::<History>()
+        .init_resource::<GeneratorSettings>()
+        .init_resource::<HelpPage>()
+        .add_systems(
+            Startup,
+            (
+                setup,
+                hud_setup,
+                diagnostics::diagnostics_hud_setup,
+                plot::plot_setup,
+                inspector::inspector_setup,
+                potential_field::potential_field_setup,
+                spacetime_grid::spacetime_grid_setup,
+                body_color::color_legend_setup,
+                launch_preview::launch_readout_setup,
+            ),
+        )
         .add_systems(
             Update,
             (
-                update_bodies,
-                compute_gravity_system,
-                elastic_collision_system,
-                body_sprite_system,
-                camera_control_system,
+                (
+                    update_bodies,
+                    compute_gravity_system,
+                    sph::sph_system,
+                    elastic_collision_system,
+                )
+                    .run_if(simulation_running),
+                body_sprite_system.after(view_frame::view_frame_system),
+                (
+                    camera::camera_follow_system.after(view_frame::view_frame_system),
+                    camera::camera_control_system.after(camera::camera_follow_system),
+                ),
                 hud_update_system,
-                editor_input_system,
+                (
+                    editor_input_system,
+                    simulation_input_system,
+                    grab::grab_system
+                        .after(update_bodies)
+                        .before(view_frame::view_frame_system),
+                    clipboard::clipboard_system.after(editor_input_system),
+                    history::history_input_system,
+                    generators::generator_input_system,
+                ),
+                diagnostics::conservation_diagnostics_system,
+                diagnostics::diagnostics_hud_update_system,
+                (
+                    selection::selection_cleanup_system,
+                    selection::selection_highlight_system,
+                    selection::box_select_system,
+                    selection::selection_delete_system,
+                ),
+                plot::plot_sample_system.run_if(simulation_running),
+                plot::plot_draw_system,
+                plot::plot_input_system,
+                inspector::inspector_update_system,
+                inspector::orbit_primary_input_system,
+                inspector::inspector_edit_system,
+                (
+                    view_frame::view_frame_system.after(update_bodies),
+                    view_frame::view_frame_input_system,
+                ),
+                (
+                    (
+                        trajectory::trajectory_preview_system,
+                        trajectory::trajectory_input_system,
+                        launch_preview::launch_preview_system,
+                    ),
+                    (
+                        trail::trail_attach_system,
+                        trail::trail_record_system
+                            .after(view_frame::view_frame_system)
+                            .run_if(simulation_running),
+                        trail::trail_draw_system,
+                        trail::trail_input_system,
+                    ),
+                    (
+                        vector_overlay::vector_overlay_system,
+                        vector_overlay::vector_overlay_input_system,
+                    ),
+                    (
+                        potential_field::potential_field_system,
+                        potential_field::potential_field_input_system,
+                    ),
+                    (
+                        spacetime_grid::spacetime_grid_system,
+                        spacetime_grid::spacetime_grid_input_system,
+                    ),
+                    (
+                        body_color::body_color_range_system,
+                        body_color::body_color_input_system,
+                        body_color::color_legend_update_system,
+                    ),
+                ),
             ),
         ) // Add elastic_collision_system
         .run();
 }
 
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
+fn setup(mut commands: Commands, body_assets: Res<BodyAssets>) {
     commands.spawn(Camera2dBundle::default());
 
-    // Create a common circle mesh
-    let circle_mesh = meshes.add(Circle::new(1.0)); // Unit circle
-
     // Spawn a few bodies for testing
-    commands.spawn((
+    spawn_body(
+        &mut commands,
+        &body_assets,
         Body::new(0.0, 0.0, 0.0, 0.0, 1000.0, 50.0),
-        MaterialMesh2dBundle {
-            mesh: circle_mesh.clone().into(),
-            material: materials.add(ColorMaterial::from(Color::rgb(1.0, 1.0, 1.0))),
-            transform: Transform::from_xyz(0.0, 0.0, 0.0).with_scale(Vec3::splat(50.0)), // Scale to initial size
-            ..default()
-        },
-    ));
-
-    commands.spawn((
-        Body::new(200.0, 0.0, 0.0, 2.0, 1.0, 20.0),
-        MaterialMesh2dBundle {
-            mesh: circle_mesh.clone().into(),
-            material: materials.add(ColorMaterial::from(Color::rgb(0.5, 0.5, 1.0))),
-            transform: Transform::from_xyz(200.0, 0.0, 0.0).with_scale(Vec3::splat(20.0)),
-            ..default()
-        },
-    ));
-
-    commands.spawn((
-        Body::new(-200.0, 0.0, 0.0, -2.0, 1.0, 20.0),
-        MaterialMesh2dBundle {
-            mesh: circle_mesh.clone().into(),
-            material: materials.add(ColorMaterial::from(Color::rgb(1.0, 0.5, 0.5))),
-            transform: Transform::from_xyz(-200.0, 0.0, 0.0).with_scale(Vec3::splat(20.0)),
-            ..default()
-        },
-    ));
+    );
+
+    let mut body = Body::new(200.0, 0.0, 0.0, 2.0, 1.0, 20.0);
+    body.color = Color::rgb(0.5, 0.5, 1.0);
+    spawn_body(&mut commands, &body_assets, body);
+
+    let mut body = Body::new(-200.0, 0.0, 0.0, -2.0, 1.0, 20.0);
+    body.color = Color::rgb(1.0, 0.5, 0.5);
+    spawn_body(&mut commands, &body_assets, body);
 }
 
-fn update_bodies(mut query: Query<&mut Body>, time: Res<Time>) {
-    let mult = time.delta_seconds() * 400.0; // Equivalent to original time_mult
+/// Held bodies are moved by `grab::grab_system` instead.
+fn update_bodies(mut query: Query<&mut Body, Without<Held>>, time: Res<Time>) {
+    let mult = time.delta_seconds() * TIME_SCALE; // Equivalent to original time_mult
 
     for mut body in query.iter_mut() {
         body.past_x = body.x;
@@ -86,6 +216,8 @@ fn update_bodies(mut query: Query<&mut Body>, time: Res<Time>) {
 
         body.past_a_x = body.a_x * mult;
         body.past_a_y = body.a_y * mult;
+        body.last_a_x = body.a_x;
+        body.last_a_y = body.a_y;
 
         // Reset acceleration for next frame
         body.a_x = 0.0;
@@ -93,132 +225,233 @@ fn update_bodies(mut query: Query<&mut Body>, time: Res<Time>) {
     }
 }
 
-fn compute_gravity_system(mut query: Query<&mut Body>) {
-    let mut bodies = query.iter_mut().collect::<Vec<Mut<Body>>>();
-    let num_bodies = bodies.len();
+fn compute_gravity_system(
+    mut query: Query<&mut Body>,
+    solver: Res<GravitySolver>,
+    mut particle_mesh: ResMut<ParticleMesh>,
+    fmm: Res<Fmm>,
+    mut force_error: ResMut<ForceErrorComparison>,
+    mut gpu: GpuGravityParam,
+    time: Res<Time>,
+) {
+    if *solver == GravitySolver::ParticleMesh && particle_mesh.wrap_bodies {
+        for mut body in query.iter_mut() {
+            let pos = Vec2::new(body.x, body.y);
+            let wrapped = particle_mesh.wrap(pos);
+            if wrapped != pos {
+                body.x = wrapped.x;
+                body.y = wrapped.y;
+            }
+        }
+    }
 
-    for i in 0..num_bodies {
-        for j in (i + 1)..num_bodies {
-            let (mut body1, mut body2) = {
-                let (b1, b2) = bodies.split_at_mut(j);
-                (b1[i].as_mut(), b2[0].as_mut())
-            };
+    let points = query
+        .iter()
+        .map(|body| (Vec2::new(body.x, body.y), body.mass))
+        .collect::<Vec<_>>();
+    let accelerations = match *solver {
+        GravitySolver::Pairwise => pairwise_accelerations(&points),
+        GravitySolver::ParticleMesh => particle_mesh.accelerations(&points),
+        GravitySolver::Fmm => fmm.accelerations(&points),
+        GravitySolver::Gpu => gpu
+            .accelerations(&points)
+            .unwrap_or_else(|| pairwise_accelerations(&points)),
+    };
+
+    // Periodically measure the active solver against the direct sum
+    if force_error.enabled && force_error.timer.tick(time.delta()).just_finished() {
+        let direct = pairwise_accelerations(&points);
+        force_error.rms = Some(rms_force_error(&points, &accelerations, &direct));
+    }
+
+    for (mut body, acc) in query.iter_mut().zip(accelerations) {
+        body.a_x += acc.x;
+        body.a_y += acc.y;
+    }
+}
+
+/// Direct O(N^2) sum of the gravitational acceleration on every
+/// `(position, mass)` pair.
+fn pairwise_accelerations(bodies: &[(Vec2, f32)]) -> Vec<Vec2> {
+    let mut accelerations = vec![Vec2::ZERO; bodies.len()];
+
+    for i in 0..bodies.len() {
+        for j in (i + 1)..bodies.len() {
+            let (pos1, mass1) = bodies[i];
+            let (pos2, mass2) = bodies[j];
 
             let min_distance = 0.0001;
-            let direction = (body2.x - body1.x, body2.y - body1.y);
-            let mut distance = ((body2.x - body1.x).powi(2) + (body2.y - body1.y).powi(2)).sqrt();
+            let direction = pos2 - pos1;
+            let mut distance = direction.length();
             if distance < min_distance {
                 distance = min_distance;
             }
-            let unit_direction = (direction.0 / distance, direction.1 / distance);
-            let force_scalar = GRAVITY_CONST * body1.mass * body2.mass / distance.powi(2);
+            let unit_direction = direction / distance;
+            let force_scalar = GRAVITY_CONST * mass1 * mass2 / distance.powi(2);
 
             // Apply force to body1
-            let acc_scalar1 = force_scalar / body1.mass;
-            body1.a_x += unit_direction.0 * acc_scalar1;
-            body1.a_y += unit_direction.1 * acc_scalar1;
+            accelerations[i] += unit_direction * (force_scalar / mass1);
 
             // Apply opposite force to body2
-            let acc_scalar2 = force_scalar / body2.mass;
-            body2.a_x -= unit_direction.0 * acc_scalar2;
-            body2.a_y -= unit_direction.1 * acc_scalar2;
+            accelerations[j] -= unit_direction * (force_scalar / mass2);
         }
     }
+
+    accelerations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_path_matches_newton() {
+        let accelerations =
+            pairwise_accelerations(&[(Vec2::ZERO, 1000.0), (Vec2::new(100.0, 0.0), 2.0)]);
+        let expected = GRAVITY_CONST / 100.0f32.powi(2);
+
+        assert!((accelerations[0].x - expected * 2.0).abs() < 1e-9);
+        assert!((accelerations[1].x + expected * 1000.0).abs() < 1e-7);
+        assert_eq!(accelerations[0].y, 0.0);
+    }
+}
+
+/// RMS error of the forces `m * a` relative to the RMS of the reference forces.
+fn rms_force_error(bodies: &[(Vec2, f32)], accelerations: &[Vec2], reference: &[Vec2]) -> f32 {
+    let mut error = 0.0;
+    let mut norm = 0.0;
+    for ((&(_, mass), acc), reference) in bodies.iter().zip(accelerations).zip(reference) {
+        error += ((*acc - *reference) * mass).length_squared() as f64;
+        norm += (*reference * mass).length_squared() as f64;
+    }
+    if norm > 0.0 {
+        (error / norm).sqrt() as f32
+    } else {
+        0.0
+    }
 }
 
 fn body_sprite_system(
-    mut query: Query<(&Body, &mut Transform, &mut Handle<ColorMaterial>)>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<(&Body, &mut Transform, &mut Sprite)>,
+    frame: Res<ViewFrame>,
+    coloring: Res<BodyColoring>,
 ) {
-    for (body, mut transform, mut material_handle) in query.iter_mut() {
-        transform.translation.x = body.x;
-        transform.translation.y = body.y;
+    for (body, mut transform, mut sprite) in query.iter_mut() {
+        // Place the sprite where the body appears in the view frame
+        let view_pos = frame.transform.to_view(Vec2::new(body.x, body.y));
+        transform.translation.x = view_pos.x;
+        transform.translation.y = view_pos.y;
         // Set Z to 0 for 2D rendering
         transform.translation.z = 0.0;
         // Update sprite size based on body size
         transform.scale = Vec3::splat(body.size); // Scale the unit circle to the body's size
 
         // Update color
-        if let Some(material) = materials.get_mut(&*material_handle) {
-            material.color = body.color;
-        }
+        sprite.color = coloring.color(body);
     }
 }
 
-fn camera_control_system(
-    mut camera_query: Query<&mut Transform, With<Camera2d>>,
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut mouse_wheel_events: EventReader<MouseWheel>,
-    time: Res<Time>,
-) {
-    let mut camera_transform = camera_query.single_mut();
-    let mut camera_translation = camera_transform.translation;
-    let mut camera_scale = camera_transform.scale.x; // Assuming uniform scale
+#[derive(Component)]
+struct HudControlsText; // New component
 
-    let camera_speed = 500.0 * time.delta_seconds();
-    let zoom_speed = 0.1f32; // Corrected to f32
+/// Page of the controls HUD. H steps through the pages and then hides the
+/// HUD, so each page fits on a small window.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+enum HelpPage {
+    /// Simulation, camera and overlay controls.
+    #[default]
+    Simulation,
+    /// Spawning, selection, editing and generators.
+    Editor,
+    Hidden,
+}
 
-    // Keyboard pan
-    if keyboard_input.pressed(KeyCode::KeyW) {
-        camera_translation.y += camera_speed / camera_scale;
-    }
-    if keyboard_input.pressed(KeyCode::KeyS) {
-        camera_translation.y -= camera_speed / camera_scale;
-    }
-    if keyboard_input.pressed(KeyCode::KeyA) {
-        camera_translation.x -= camera_speed / camera_scale;
-    }
-    if keyboard_input.pressed(KeyCode::KeyD) {
-        camera_translation.x += camera_speed / camera_scale;
+impl HelpPage {
+    fn next(self) -> Self {
+        match self {
+            HelpPage::Simulation => HelpPage::Editor,
+            HelpPage::Editor => HelpPage::Hidden,
+            HelpPage::Hidden => HelpPage::Simulation,
+        }
     }
+}
 
-    // Mouse wheel zoom
-    for event in mouse_wheel_events.read() {
-        let scroll_delta = event.y;
-        let old_scale = camera_scale;
-        camera_scale *= (1.0 + zoom_speed).powf(-scroll_delta);
+/// Everything the controls HUD shows the state of.
+#[derive(SystemParam)]
+struct HudState<'w> {
+    page: Res<'w, HelpPage>,
+    elastic_collisions_enabled: Res<'w, ElasticCollisionsEnabled>,
+    solver: Res<'w, GravitySolver>,
+    fmm: Res<'w, Fmm>,
+    force_error: Res<'w, ForceErrorComparison>,
+    gpu: Res<'w, GpuGravity>,
+    selected_body_state: Res<'w, SelectedBodyState>,
+    trails: Res<'w, TrailSettings>,
+    view_frame: Res<'w, ViewFrame>,
+    paused: Res<'w, SimulationPaused>,
+    generators: Res<'w, GeneratorSettings>,
+}
 
-        // Adjust camera position to zoom towards the center of the screen
-        // This is a simplified approach. A more accurate one would involve mouse position.
-        camera_translation.x = camera_translation.x * (camera_scale / old_scale);
-        camera_translation.y = camera_translation.y * (camera_scale / old_scale);
+impl HudState<'_> {
+    fn controls_text(&self) -> String {
+        match *self.page {
+            HelpPage::Simulation => {
+                let force_error_text = match (self.force_error.enabled, self.force_error.rms) {
+                    (false, _) => "OFF".to_string(),
+                    (true, None) => "...".to_string(),
+                    (true, Some(rms)) => format!("RMS {:.2E}", rms),
+                };
+                format!(
+                    "H: HELP PAGE 1/2 (SIMULATION)\nR: RESET\nSPACE: PAUSE ({})\nSCROLL: ZOOM TO CURSOR\nRMB/MMB DRAG: PAN\nL/SHIFT+L: LOCK SELECTED/BARYCENTER\nF: FIT ALL\nE: TOGGLE ELASTIC ({})\nG: SOLVER ({})\nK: FORCE ERROR ({})\nSHIFT+K: FMM ORDER ({})\nP: ORBIT PREVIEW\nT: TRAILS ({})\nSHIFT+T/CTRL+T: TRAIL FADE/COLOR ({})\n[/]: TRAIL LENGTH ({})\n,/.: TRAIL WIDTH ({:.1})\nN/SHIFT+N: TRAIL/VIEW FRAME ({}/{})\nU/I/J: VELOCITY/ACCEL/PAIR ARROWS\n-/=: ARROW SCALE (SHIFT: ACCEL)\nB/SHIFT+B: COLOR MODE/COLORMAP\nF1/F2/F3: CHARTS/PAUSE/EXPORT\nF5/F6/F7/F8: HEATMAP/FIELD/CONTOURS/COLORMAP\nF9: SPACETIME GRID",
+                    if self.paused.0 { "PAUSED" } else { "RUNNING" },
+                    if self.elastic_collisions_enabled.0 {
+                        "ENABLED"
+                    } else {
+                        "DISABLED"
+                    },
+                    if *self.solver == GravitySolver::Gpu && self.gpu.fallback {
+                        "GPU (CPU FALLBACK)"
+                    } else {
+                        self.solver.label()
+                    },
+                    force_error_text,
+                    self.fmm.order,
+                    if self.trails.enabled { "ON" } else { "OFF" },
+                    self.trails.color.label(),
+                    self.trails.length,
+                    self.trails.width,
+                    self.trails.frame.label(),
+                    self.view_frame.kind.label(),
+                )
+            }
+            HelpPage::Editor => format!(
+                "H: HELP PAGE 2/2 (EDITOR)\nZ/X: CHANGE SIZE\nC/V: CHANGE DENSITY\nQ: SPAWN ({})\nO: SPAWN VELOCITY ({})\nPGUP/PGDN: ORBIT ECCENTRICITY ({:.2})\nALT+DRAG: FINE LAUNCH VELOCITY\nCLICK: SELECT (SHIFT ADDS)\nSHIFT+DRAG: BOX SELECT\nESC: CLEAR SELECTION\nDEL: DELETE SELECTED\nDRAG BODY: MOVE/THROW\nCTRL+C/V/D: COPY/PASTE/DUPLICATE\nCTRL+LEFT/RIGHT: ROTATE PASTE\nCTRL+Z/CTRL+Y: UNDO/REDO\nY: PIN/UNPIN ORBIT PRIMARY\nUP/DOWN: INSPECTOR FIELD\nLEFT/RIGHT: EDIT (SHIFT COARSE)\n1-5: DISK/PLUMMER/RING/GALAXY/FIELD ({})\n6/7: GEN COUNT ({})\n8/9: GEN RADIUS ({:.0})\nM: GEN MASS ({})\n0: GEN SEED ({})",
+                if self.selected_body_state.spawn_gas {
+                    "GAS CLOUD"
+                } else {
+                    "BODY"
+                },
+                self.selected_body_state.velocity_mode.label(),
+                self.selected_body_state.eccentricity,
+                self.generators
+                    .last
+                    .map_or("NONE", |generator| generator.label()),
+                self.generators.count,
+                self.generators.radius,
+                self.generators.mass.label(),
+                self.generators.seed,
+            ),
+            HelpPage::Hidden => String::new(),
+        }
     }
-
-    camera_transform.translation = camera_translation;
-    camera_transform.scale = Vec3::splat(camera_scale);
 }
 
-#[derive(Component)]
-struct HudText;
-
-#[derive(Component)]
-struct HudControlsText; // New component
-
-fn hud_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn hud_setup(mut commands: Commands, asset_server: Res<AssetServer>, hud: HudState) {
     let font = asset_server.load("fonts/start.ttf"); // Assuming font is in assets/fonts/start.ttf
 
-    commands
-        .spawn(
-            TextBundle::from_section(
-                "FPS: ",
-                TextStyle {
-                    font: font.clone(),
-                    font_size: 20.0,
-                    color: Color::WHITE,
-                },
-            )
-            .with_style(Style {
-                position_type: PositionType::Absolute,
-                top: Val::Px(10.0),
-                left: Val::Px(10.0),
-                ..default()
-            }),
-        )
-        .insert(HudText);
-
     commands.spawn(
         TextBundle::from_section(
-            "R: RESET\nH: TOGGLE HUD\nSCROLL: ZOOM\nZ/X: CHANGE SIZE\nC/V: CHANGE DENSITY\nE: TOGGLE ELASTIC (DISABLED)", // Updated text
+            hud.controls_text(),
             TextStyle {
                 font: font.clone(),
                 font_size: 16.0,
@@ -234,20 +467,38 @@ fn hud_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     ).insert(HudControlsText); // Insert new component
 }
 
-fn hud_update_system(
-    mut query: Query<(&mut Text, Option<&HudText>, Option<&HudControlsText>)>, // Combined query
-    time: Res<Time>,
-    elastic_collisions_enabled: Res<ElasticCollisionsEnabled>,
-) {
-    for (mut text, is_fps_text, is_controls_text) in query.iter_mut() {
-        if is_fps_text.is_some() {
-            text.sections[0].value = format!("FPS: {:.0}", 1.0 / time.delta_seconds());
-        } else if is_controls_text.is_some() {
-            let controls_text = format!(
-                "R: RESET\nH: TOGGLE HUD\nSCROLL: ZOOM\nZ/X: CHANGE SIZE\nC/V: CHANGE DENSITY\nE: TOGGLE ELASTIC ({})",
-                if elastic_collisions_enabled.0 { "ENABLED" } else { "DISABLED" }
-            );
-            text.sections[0].value = controls_text;
+fn hud_update_system(mut query: Query<&mut Text, With<HudControlsText>>, hud: HudState) {
+    for mut text in query.iter_mut() {
+        text.sections[0].value = hud.controls_text();
+    }
+}
+
+/// How `editor_input_system` picks the velocity of a spawned body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum SpawnVelocity {
+    /// Proportional to the drag.
+    #[default]
+    Drag,
+    /// Orbiting the body pulling hardest on the spawn point.
+    Attractor,
+    /// Orbiting the center of mass of all bodies.
+    Barycenter,
+}
+
+impl SpawnVelocity {
+    fn next(self) -> Self {
+        match self {
+            SpawnVelocity::Drag => SpawnVelocity::Attractor,
+            SpawnVelocity::Attractor => SpawnVelocity::Barycenter,
+            SpawnVelocity::Barycenter => SpawnVelocity::Drag,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SpawnVelocity::Drag => "DRAG",
+            SpawnVelocity::Attractor => "ORBIT ATTRACTOR",
+            SpawnVelocity::Barycenter => "ORBIT BARYCENTER",
         }
     }
 }
@@ -259,13 +510,135 @@ struct SelectedBodyState {
     selected_vel: Vec2,
     selected_size: f32,
     selected_density: f32,
+    spawn_gas: bool,
+    velocity_mode: SpawnVelocity,
+    /// Eccentricity of orbits set up by the orbit spawn modes.
+    eccentricity: f32,
+}
+
+impl SelectedBodyState {
+    /// Drag length in view units per unit of launch velocity. Fine drags,
+    /// with Alt held, are ten times longer for the same velocity.
+    fn drag_scale(fine: bool) -> f32 {
+        if fine { 500.0 } else { 50.0 }
+    }
+
+    /// Velocity given to a body dragged from `selected_pos` to `end_pos`.
+    fn launch_velocity(&self, end_pos: Vec2, fine: bool) -> Vec2 {
+        (end_pos - self.selected_pos) / Self::drag_scale(fine)
+    }
+
+    /// World start position and velocity of a body launched by dragging from
+    /// `selected_pos` to `end_pos`, both in view coordinates.
+    fn launch<'a>(
+        &self,
+        end_pos: Vec2,
+        fine: bool,
+        frame: FrameTransform,
+        bodies: impl Iterator<Item = (Entity, &'a Body)>,
+    ) -> (Vec2, Vec2) {
+        let start = frame.to_world(self.selected_pos);
+        let drag = frame.vector_to_world(self.launch_velocity(end_pos, fine));
+        (start, self.spawn_velocity(start, drag, bodies))
+    }
+
+    /// Velocity of a body spawned at `start` with drag velocity `drag`, both
+    /// in world coordinates. The orbit modes treat the new body as a test
+    /// particle at periapsis and only use the drag to pick the direction of
+    /// the orbit; without anything to orbit the drag velocity is used.
+    fn spawn_velocity<'a>(
+        &self,
+        start: Vec2,
+        drag: Vec2,
+        bodies: impl Iterator<Item = (Entity, &'a Body)>,
+    ) -> Vec2 {
+        let primary = match self.velocity_mode {
+            SpawnVelocity::Drag => return drag,
+            SpawnVelocity::Attractor => orbit::strongest_pull(start, bodies)
+                .and_then(|(_, body)| CenterOfMass::of(std::iter::once(body))),
+            SpawnVelocity::Barycenter => CenterOfMass::of(bodies.map(|(_, body)| body)),
+        };
+        let Some(primary) = primary else {
+            return drag;
+        };
+        let r = start - primary.pos;
+        let clockwise = r.perp_dot(drag) < 0.0;
+        primary.vel
+            + orbit::periapsis_velocity(
+                r,
+                GRAVITY_CONST * primary.mass,
+                self.eccentricity,
+                clockwise,
+            )
+    }
 }
 
 #[derive(Resource, Default)]
 struct ElasticCollisionsEnabled(bool);
 
+/// Freezes the physics systems; editing and rendering keep running.
+#[derive(Resource, Default)]
+struct SimulationPaused(bool);
+
+fn simulation_running(paused: Res<SimulationPaused>) -> bool {
+    !paused.0
+}
+
+/// Which method `compute_gravity_system` uses to find accelerations.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+enum GravitySolver {
+    /// Direct O(N^2) sum over every pair of bodies.
+    #[default]
+    Pairwise,
+    /// FFT-based particle-mesh solver on a periodic grid.
+    ParticleMesh,
+    /// Fast multipole method with a configurable expansion order.
+    Fmm,
+    /// All-pairs sum in a compute shader, falling back to the CPU.
+    Gpu,
+}
+
+impl GravitySolver {
+    fn next(self) -> Self {
+        match self {
+            GravitySolver::Pairwise => GravitySolver::ParticleMesh,
+            GravitySolver::ParticleMesh => GravitySolver::Fmm,
+            GravitySolver::Fmm => GravitySolver::Gpu,
+            GravitySolver::Gpu => GravitySolver::Pairwise,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            GravitySolver::Pairwise => "PAIRWISE",
+            GravitySolver::ParticleMesh => "PARTICLE MESH",
+            GravitySolver::Fmm => "FMM",
+            GravitySolver::Gpu => "GPU",
+        }
+    }
+}
+
+/// Comparison mode that reports how far the active solver's forces are from
+/// the direct pairwise sum.
+#[derive(Resource)]
+struct ForceErrorComparison {
+    enabled: bool,
+    timer: Timer,
+    rms: Option<f32>,
+}
+
+impl Default for ForceErrorComparison {
+    fn default() -> Self {
+        ForceErrorComparison {
+            enabled: false,
+            timer: Timer::from_seconds(0.5, TimerMode::Repeating),
+            rms: None,
+        }
+    }
+}
+
 fn elastic_collision_system(
-    mut query: Query<&mut Body>,
+    mut query: Query<&mut Body, Without<GasParticle>>,
     elastic_collisions_enabled: Res<ElasticCollisionsEnabled>,
 ) {
     if !elastic_collisions_enabled.0 {
@@ -310,6 +683,49 @@ fn elastic_collision_system(
     }
 }
 
+fn simulation_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut elastic_collisions_enabled: ResMut<ElasticCollisionsEnabled>,
+    mut solver: ResMut<GravitySolver>,
+    mut fmm: ResMut<Fmm>,
+    mut force_error: ResMut<ForceErrorComparison>,
+    mut paused: ResMut<SimulationPaused>,
+    mut help_page: ResMut<HelpPage>,
+) {
+    // Step through the help pages
+    if keyboard_input.just_pressed(KeyCode::KeyH) {
+        *help_page = help_page.next();
+    }
+
+    // Pause or resume the physics
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        paused.0 = !paused.0;
+    }
+
+    // Toggle elastic collisions
+    if keyboard_input.just_pressed(KeyCode::KeyE) {
+        elastic_collisions_enabled.0 = !elastic_collisions_enabled.0;
+    }
+
+    // Cycle gravity solver
+    if keyboard_input.just_pressed(KeyCode::KeyG) {
+        *solver = solver.next();
+        info!("Gravity solver: {:?}", *solver);
+    }
+
+    // Force error comparison and FMM expansion order
+    if keyboard_input.just_pressed(KeyCode::KeyK) {
+        let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        if shift {
+            fmm.cycle_order();
+            info!("FMM order: {}", fmm.order);
+        } else {
+            force_error.enabled = !force_error.enabled;
+            force_error.rms = None;
+        }
+    }
+}
+
 fn editor_input_system(
     mut commands: Commands,
     windows: Query<&Window>,
@@ -317,11 +733,15 @@ fn editor_input_system(
     mouse_button_input: Res<ButtonInput<MouseButton>>,
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mut selected_body_state: ResMut<SelectedBodyState>,
-    mut body_query: Query<Entity, With<Body>>,
-    mut camera_transform_query: Query<&mut Transform, With<Camera2d>>,
-    mut elastic_collisions_enabled: ResMut<ElasticCollisionsEnabled>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut body_query: Query<(Entity, &Body)>,
+    camera_transform_query: Query<&Transform, With<Camera2d>>,
+    mut camera_controller: ResMut<CameraController>,
+    mut selection: ResMut<Selection>,
+    sph_settings: Res<SphSettings>,
+    body_assets: Res<BodyAssets>,
+    view_frame: Res<ViewFrame>,
+    clipboard: Res<Clipboard>,
+    mut recorder: HistoryRecorder,
 ) {
     let window = windows.single();
     let (camera, camera_transform) = camera_query.single();
@@ -333,37 +753,58 @@ fn editor_input_system(
             .map(|ray| ray.origin.truncate())
     });
 
-    // Debug logging
-    if let Some(pos) = mouse_world_pos {
-        info!("Mouse world position: {:?}", pos);
-    } else {
-        info!("Mouse outside window or no position.");
-    }
-
     // Reset simulation
     if keyboard_input.just_pressed(KeyCode::KeyR) {
-        for entity in body_query.iter() {
+        recorder.despawned(body_query.iter());
+        for (entity, _) in body_query.iter() {
             commands.entity(entity).despawn();
         }
-        let mut cam_transform = camera_transform_query.single_mut();
-        cam_transform.translation = Vec3::ZERO;
-        cam_transform.scale = Vec3::ONE;
+        selection.clear();
+        camera_controller.reset();
         selected_body_state.pos_selected = false;
         selected_body_state.selected_size = 50.0;
         selected_body_state.selected_density = 1.0;
     }
 
-    // Toggle elastic collisions
-    if keyboard_input.just_pressed(KeyCode::KeyE) {
-        elastic_collisions_enabled.0 = !elastic_collisions_enabled.0;
+    // Toggle between spawning bodies and gas clouds
+    if keyboard_input.just_pressed(KeyCode::KeyQ) {
+        selected_body_state.spawn_gas = !selected_body_state.spawn_gas;
     }
 
-    // Record start position when mouse is pressed
-    if mouse_button_input.just_pressed(MouseButton::Left) {
+    // Spawn velocity mode and the eccentricity of assisted orbits
+    if keyboard_input.just_pressed(KeyCode::KeyO) {
+        selected_body_state.velocity_mode = selected_body_state.velocity_mode.next();
+    }
+    if keyboard_input.just_pressed(KeyCode::PageUp) {
+        selected_body_state.eccentricity = (selected_body_state.eccentricity + 0.05).min(0.95);
+    }
+    if keyboard_input.just_pressed(KeyCode::PageDown) {
+        selected_body_state.eccentricity = (selected_body_state.eccentricity - 0.05).max(0.0);
+    }
+
+    // Esc while pasting only cancels the paste
+    if keyboard_input.just_pressed(KeyCode::Escape) && !clipboard.pasting() {
+        selection.clear();
+    }
+
+    // Select the body under the cursor, otherwise record start position.
+    // Shift on empty space starts a box selection instead.
+    if mouse_button_input.just_pressed(MouseButton::Left) && !clipboard.pasting() {
         if let Some(pos) = mouse_world_pos {
-            selected_body_state.pos_selected = true;
-            selected_body_state.selected_pos = pos;
-            info!("Start pos: {:?}", pos);
+            let pick_radius = 6.0 * camera_transform_query.single().scale.x;
+            let world_pos = view_frame.transform.to_world(pos);
+            let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+            if let Some(entity) = selection::pick(body_query.iter(), world_pos, pick_radius) {
+                if shift {
+                    selection.toggle(entity);
+                } else {
+                    selection.select_only(entity);
+                }
+            } else if !shift {
+                selected_body_state.pos_selected = true;
+                selected_body_state.selected_pos = pos;
+                info!("Start pos: {:?}", pos);
+            }
         }
     }
 
@@ -371,60 +812,72 @@ fn editor_input_system(
     if mouse_button_input.just_released(MouseButton::Left) {
         if let Some(end_pos) = mouse_world_pos {
             if selected_body_state.pos_selected {
-                let velocity = (end_pos - selected_body_state.selected_pos) / 50.0;
+                // The drag happens in the view frame; spawn in world coordinates
+                let fine = keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+                let (start_pos, velocity) = selected_body_state.launch(
+                    end_pos,
+                    fine,
+                    view_frame.transform,
+                    body_query.iter(),
+                );
                 info!("End pos: {:?}, Velocity: {:?}", end_pos, velocity);
 
-                commands.spawn((
-                    Body::new(
-                        selected_body_state.selected_pos.x,
-                        selected_body_state.selected_pos.y,
-                        velocity.x,
-                        velocity.y,
-                        selected_body_state.selected_density,
-                        selected_body_state.selected_size,
-                    ),
-                    MaterialMesh2dBundle {
-                        mesh: meshes.add(Circle::new(1.0)).into(),
-                        material: materials.add(ColorMaterial::from(Color::WHITE)),
-                        transform: Transform::from_xyz(
-                            selected_body_state.selected_pos.x,
-                            selected_body_state.selected_pos.y,
-                            0.0,
-                        )
-                        .with_scale(Vec3::splat(selected_body_state.selected_size)),
-                        ..default()
-                    },
-                ));
+                let spawned = if selected_body_state.spawn_gas {
+                    GasCloud {
+                        center: start_pos,
+                        velocity,
+                        radius: selected_body_state.selected_size * 4.0,
+                        density: selected_body_state.selected_density,
+                        particles: sph_settings.cloud_particles,
+                    }
+                    .spawn(&mut commands, &body_assets)
+                } else {
+                    let entity = spawn_body(
+                        &mut commands,
+                        &body_assets,
+                        Body::new(
+                            start_pos.x,
+                            start_pos.y,
+                            velocity.x,
+                            velocity.y,
+                            selected_body_state.selected_density,
+                            selected_body_state.selected_size,
+                        ),
+                    );
+                    vec![entity.id()]
+                };
+                recorder.spawned(spawned);
 
                 selected_body_state.pos_selected = false;
             }
         }
     }
 
-    // Change size
+    // Change size and density, leaving Ctrl combinations to the clipboard
+    // and history
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
     let size_speed = 0.2;
-    if keyboard_input.pressed(KeyCode::KeyZ) {
+    if keyboard_input.pressed(KeyCode::KeyZ) && !ctrl {
         selected_body_state.selected_size += size_speed;
         if selected_body_state.selected_size < 1.0 {
             selected_body_state.selected_size = 1.0;
         }
     }
-    if keyboard_input.pressed(KeyCode::KeyX) {
+    if keyboard_input.pressed(KeyCode::KeyX) && !ctrl {
         selected_body_state.selected_size -= size_speed;
         if selected_body_state.selected_size < 1.0 {
             selected_body_state.selected_size = 1.0;
         }
     }
 
-    // Change density
     let density_speed = 0.1;
-    if keyboard_input.pressed(KeyCode::KeyC) {
+    if keyboard_input.pressed(KeyCode::KeyC) && !ctrl {
         selected_body_state.selected_density -= density_speed;
         if selected_body_state.selected_density < 1.0 {
             selected_body_state.selected_density = 1.0;
         }
     }
-    if keyboard_input.pressed(KeyCode::KeyV) {
+    if keyboard_input.pressed(KeyCode::KeyV) && !ctrl {
         selected_body_state.selected_density += density_speed;
         if selected_body_state.selected_density < 1.0 {
             selected_body_state.selected_density = 1.0;
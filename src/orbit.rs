@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+
+use crate::GRAVITY_CONST;
+use crate::body::Body;
+
+/// Osculating Keplerian elements of a body relative to a primary.
+#[derive(Debug, Clone, Copy)]
+pub struct OrbitalElements {
+    /// Negative for hyperbolic (unbound) orbits.
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    /// Angle of periapsis from the +x axis, in radians.
+    pub argument_of_periapsis: f32,
+    /// In simulation time units, which run `TIME_SCALE` times faster than
+    /// real seconds. `None` for unbound orbits.
+    pub period: Option<f32>,
+    pub periapsis: f32,
+    /// `None` for unbound orbits.
+    pub apoapsis: Option<f32>,
+    /// True when the body orbits clockwise around the primary.
+    pub retrograde: bool,
+}
+
+impl OrbitalElements {
+    /// Computes the elements of `body` around `primary` from their current
+    /// relative position and velocity. Returns `None` if the two coincide.
+    pub fn from_bodies(body: &Body, primary: &Body) -> Option<Self> {
+        let r = Vec2::new(body.x - primary.x, body.y - primary.y);
+        let v = Vec2::new(body.v_x - primary.v_x, body.v_y - primary.v_y);
+        Self::from_state(r, v, GRAVITY_CONST * (body.mass + primary.mass))
+    }
+
+    /// Computes elements from a relative state vector and `mu = G (M + m)`.
+    pub fn from_state(r: Vec2, v: Vec2, mu: f32) -> Option<Self> {
+        let distance = r.length();
+        if distance == 0.0 || mu <= 0.0 {
+            return None;
+        }
+
+        let energy = 0.5 * v.length_squared() - mu / distance;
+        let eccentricity_vec = ((v.length_squared() - mu / distance) * r - r.dot(v) * v) / mu;
+        let eccentricity = eccentricity_vec.length();
+        let semi_major_axis = -mu / (2.0 * energy);
+        let bound = energy < 0.0;
+
+        Some(OrbitalElements {
+            semi_major_axis,
+            eccentricity,
+            argument_of_periapsis: eccentricity_vec.y.atan2(eccentricity_vec.x),
+            period: bound
+                .then(|| 2.0 * std::f32::consts::PI * (semi_major_axis.powi(3) / mu).sqrt()),
+            periapsis: semi_major_axis * (1.0 - eccentricity),
+            apoapsis: bound.then_some(semi_major_axis * (1.0 + eccentricity)),
+            retrograde: r.perp_dot(v) < 0.0,
+        })
+    }
+}
+
+/// The body exerting the strongest pull on `target`, excluding itself.
+pub fn dominant_attractor<'a>(
+    target: Entity,
+    bodies: impl Iterator<Item = (Entity, &'a Body)>,
+) -> Option<Entity> {
+    let bodies = bodies.collect::<Vec<_>>();
+    let (_, target_body) = bodies.iter().find(|(entity, _)| *entity == target)?;
+    let target_pos = Vec2::new(target_body.x, target_body.y);
+
+    strongest_pull(
+        target_pos,
+        bodies.into_iter().filter(|(entity, _)| *entity != target),
+    )
+    .map(|(entity, _)| entity)
+}
+
+/// The body pulling hardest on a point at `pos`.
+pub fn strongest_pull<'a>(
+    pos: Vec2,
+    bodies: impl Iterator<Item = (Entity, &'a Body)>,
+) -> Option<(Entity, &'a Body)> {
+    bodies
+        .map(|(entity, body)| {
+            let distance_squared = Vec2::new(body.x, body.y).distance_squared(pos);
+            ((entity, body), body.mass / distance_squared.max(0.0001))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(pair, _)| pair)
+}
+
+/// Velocity relative to a primary that puts a test particle at offset `r`
+/// from it at the periapsis of an orbit with the given eccentricity, where
+/// `mu = G M`. Zero eccentricity gives a circular orbit. Counter-clockwise
+/// unless `clockwise`.
+pub fn periapsis_velocity(r: Vec2, mu: f32, eccentricity: f32, clockwise: bool) -> Vec2 {
+    let distance = r.length();
+    if distance == 0.0 || mu <= 0.0 {
+        return Vec2::ZERO;
+    }
+    let speed = (mu * (1.0 + eccentricity) / distance).sqrt();
+    let tangent = r.perp() / distance;
+    if clockwise {
+        -tangent * speed
+    } else {
+        tangent * speed
+    }
+}
+
+/// A body pinned as the primary for orbital elements. When unset, or when it
+/// is the selected body itself, the dominant attractor is used instead.
+#[derive(Resource, Default)]
+pub struct OrbitPrimary(pub Option<Entity>);
+
+impl OrbitPrimary {
+    /// The primary to measure `target` against: the pinned body if there is
+    /// one and it still exists, otherwise the dominant attractor.
+    pub fn resolve<'a>(
+        &self,
+        target: Entity,
+        bodies: impl Iterator<Item = (Entity, &'a Body)>,
+    ) -> Option<Entity> {
+        let bodies = bodies.collect::<Vec<_>>();
+        match self.0 {
+            Some(pinned)
+                if pinned != target && bodies.iter().any(|(entity, _)| *entity == pinned) =>
+            {
+                Some(pinned)
+            }
+            _ => dominant_attractor(target, bodies.into_iter()),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::GRAVITY_CONST;
+
+/// Particle-mesh gravity: mass is deposited onto a periodic grid with
+/// cloud-in-cell weights, the potential is found by FFT convolution with the
+/// Green's function of Poisson's equation, and the grid forces are
+/// interpolated back to the bodies with the same weights.
+#[derive(Resource)]
+pub struct ParticleMesh {
+    /// Number of cells along each side of the grid. Must be a power of two.
+    pub grid_size: usize,
+    /// Side length of the periodic domain, centered on the world origin.
+    pub domain_size: f32,
+    /// Wrap bodies that leave the domain back in on the opposite side.
+    pub wrap_bodies: bool,
+    cache: Option<PmCache>,
+}
+
+struct PmCache {
+    grid_size: usize,
+    domain_size: f32,
+    forward: Arc<dyn Fft<f32>>,
+    inverse: Arc<dyn Fft<f32>>,
+    kernel: Vec<Complex<f32>>,
+}
+
+impl Default for ParticleMesh {
+    fn default() -> Self {
+        ParticleMesh {
+            grid_size: 256,
+            domain_size: 4096.0,
+            wrap_bodies: true,
+            cache: None,
+        }
+    }
+}
+
+impl ParticleMesh {
+    pub fn cell_size(&self) -> f32 {
+        self.domain_size / self.grid_size as f32
+    }
+
+    /// Maps a world position into the periodic domain.
+    pub fn wrap(&self, pos: Vec2) -> Vec2 {
+        let half = self.domain_size * 0.5;
+        Vec2::new(
+            (pos.x + half).rem_euclid(self.domain_size) - half,
+            (pos.y + half).rem_euclid(self.domain_size) - half,
+        )
+    }
+
+    /// Computes the acceleration of every `(position, mass)` pair.
+    pub fn accelerations(&mut self, bodies: &[(Vec2, f32)]) -> Vec<Vec2> {
+        let n = self.grid_size;
+        let h = self.cell_size();
+        let half = self.domain_size * 0.5;
+        self.prepare();
+        let cache = self.cache.as_ref().unwrap();
+
+        // Deposit mass with cloud-in-cell weights
+        let mut grid = vec![Complex::new(0.0f32, 0.0); n * n];
+        let stencils: Vec<_> = bodies
+            .iter()
+            .map(|&(pos, _)| cic_stencil(pos + Vec2::splat(half), h, n))
+            .collect();
+        for (&(_, mass), stencil) in bodies.iter().zip(&stencils) {
+            for &(index, weight) in stencil {
+                grid[index].re += mass * weight;
+            }
+        }
+
+        // Convolve with the Green's function in Fourier space
+        fft_2d(&mut grid, n, &cache.forward);
+        for (cell, k) in grid.iter_mut().zip(&cache.kernel) {
+            *cell *= k;
+        }
+        fft_2d(&mut grid, n, &cache.inverse);
+        let norm = 1.0 / (n * n) as f32;
+        let potential: Vec<f32> = grid.iter().map(|c| c.re * norm).collect();
+
+        // Central differences give the acceleration on the grid
+        let mut field = vec![Vec2::ZERO; n * n];
+        for j in 0..n {
+            for i in 0..n {
+                let left = potential[j * n + (i + n - 1) % n];
+                let right = potential[j * n + (i + 1) % n];
+                let down = potential[((j + n - 1) % n) * n + i];
+                let up = potential[((j + 1) % n) * n + i];
+                field[j * n + i] = -Vec2::new(right - left, up - down) / (2.0 * h);
+            }
+        }
+
+        // Interpolate back with the same weights used for the deposit
+        stencils
+            .iter()
+            .map(|stencil| {
+                stencil
+                    .iter()
+                    .map(|&(index, weight)| field[index] * weight)
+                    .sum()
+            })
+            .collect()
+    }
+
+    fn prepare(&mut self) {
+        let n = self.grid_size;
+        if let Some(cache) = &self.cache
+            && cache.grid_size == n
+            && cache.domain_size == self.domain_size
+        {
+            return;
+        }
+        assert!(
+            n.is_power_of_two(),
+            "particle mesh grid size must be a power of two"
+        );
+
+        let mut planner = FftPlanner::new();
+        let forward = planner.plan_fft_forward(n);
+        let inverse = planner.plan_fft_inverse(n);
+
+        // -G / r using the nearest periodic image, softened to one cell at the origin
+        let h = self.cell_size();
+        let mut kernel = vec![Complex::new(0.0f32, 0.0); n * n];
+        for j in 0..n {
+            for i in 0..n {
+                let dx = i.min(n - i) as f32 * h;
+                let dy = j.min(n - j) as f32 * h;
+                let r = (dx * dx + dy * dy).sqrt().max(h);
+                kernel[j * n + i].re = -GRAVITY_CONST / r;
+            }
+        }
+        fft_2d(&mut kernel, n, &forward);
+
+        self.cache = Some(PmCache {
+            grid_size: n,
+            domain_size: self.domain_size,
+            forward,
+            inverse,
+            kernel,
+        });
+    }
+}
+
+/// Returns the four grid cells touched by a position (relative to the domain
+/// corner) together with their cloud-in-cell weights.
+fn cic_stencil(pos: Vec2, h: f32, n: usize) -> [(usize, f32); 4] {
+    let u = pos / h - Vec2::splat(0.5);
+    let base = u.floor();
+    let frac = u - base;
+    let wrap = |v: f32| (v as i64).rem_euclid(n as i64) as usize;
+    let (i0, j0) = (wrap(base.x), wrap(base.y));
+    let (i1, j1) = ((i0 + 1) % n, (j0 + 1) % n);
+    [
+        (j0 * n + i0, (1.0 - frac.x) * (1.0 - frac.y)),
+        (j0 * n + i1, frac.x * (1.0 - frac.y)),
+        (j1 * n + i0, (1.0 - frac.x) * frac.y),
+        (j1 * n + i1, frac.x * frac.y),
+    ]
+}
+
+/// In-place 2D FFT of a row-major `n` x `n` grid.
+pub fn fft_2d(grid: &mut [Complex<f32>], n: usize, fft: &Arc<dyn Fft<f32>>) {
+    fft.process(grid);
+
+    let mut column = vec![Complex::new(0.0f32, 0.0); n];
+    for i in 0..n {
+        for j in 0..n {
+            column[j] = grid[j * n + i];
+        }
+        fft.process(&mut column);
+        for j in 0..n {
+            grid[j * n + i] = column[j];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pairwise_accelerations;
+
+    #[test]
+    fn far_field_matches_direct_sum() {
+        // Many cells apart, but well inside half the periodic domain
+        let bodies = [
+            (Vec2::new(-180.3, 40.7), 500.0),
+            (Vec2::new(230.9, -25.2), 80.0),
+        ];
+        let direct = pairwise_accelerations(&bodies);
+        let mesh = ParticleMesh::default().accelerations(&bodies);
+
+        for (direct, mesh) in direct.iter().zip(&mesh) {
+            let error = (*direct - *mesh).length() / direct.length();
+            assert!(error < 0.01, "direct {direct:?} mesh {mesh:?}");
+        }
+    }
+
+    #[test]
+    fn single_body_feels_no_force() {
+        let mut pm = ParticleMesh::default();
+        for pos in [
+            Vec2::ZERO,
+            Vec2::new(123.4, -56.7),
+            Vec2::new(-2000.0, 1999.0),
+        ] {
+            let acc = pm.accelerations(&[(pos, 1000.0)]);
+            assert!(acc[0].length() < 1e-9, "self-force {:?} at {pos:?}", acc[0]);
+        }
+    }
+
+    #[test]
+    fn fft_round_trip() {
+        let n = 16;
+        let mut planner = FftPlanner::new();
+        let forward = planner.plan_fft_forward(n);
+        let inverse = planner.plan_fft_inverse(n);
+        let original: Vec<_> = (0..n * n)
+            .map(|i| Complex::new((i as f32 * 0.37).sin(), (i % 5) as f32))
+            .collect();
+
+        let mut grid = original.clone();
+        fft_2d(&mut grid, n, &forward);
+        fft_2d(&mut grid, n, &inverse);
+
+        let norm = 1.0 / (n * n) as f32;
+        for (before, after) in original.iter().zip(&grid) {
+            assert!(
+                (*before - *after * norm).norm() < 1e-5,
+                "{before} became {after}"
+            );
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use bevy::prelude::*;
+
+use crate::body::Body;
+use crate::diagnostics::ConservationDiagnostics;
+use crate::selection::Selection;
+
+const MAX_SAMPLES: usize = 600;
+const CHART_WIDTH: f32 = 300.0;
+const CHART_HEIGHT: f32 = 70.0;
+const LABEL_HEIGHT: f32 = 14.0;
+const CHART_GAP: f32 = 12.0;
+const MARGIN: f32 = 10.0;
+
+/// A quantity plotted over time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Series {
+    Fps,
+    EnergyError,
+    Separation,
+    Speed,
+    OrbitalRadius,
+}
+
+impl Series {
+    pub const ALL: [Series; 5] = [
+        Series::Fps,
+        Series::EnergyError,
+        Series::Separation,
+        Series::Speed,
+        Series::OrbitalRadius,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Series::Fps => "FPS",
+            Series::EnergyError => "ENERGY ERROR",
+            Series::Separation => "SEPARATION",
+            Series::Speed => "SPEED",
+            Series::OrbitalRadius => "ORBITAL RADIUS",
+        }
+    }
+
+    fn csv_name(self) -> &'static str {
+        match self {
+            Series::Fps => "fps",
+            Series::EnergyError => "energy_error",
+            Series::Separation => "separation",
+            Series::Speed => "speed",
+            Series::OrbitalRadius => "orbital_radius",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Series::Fps => Color::GRAY,
+            Series::EnergyError => Color::ORANGE_RED,
+            Series::Separation => Color::CYAN,
+            Series::Speed => Color::YELLOW_GREEN,
+            Series::OrbitalRadius => Color::VIOLET,
+        }
+    }
+}
+
+/// One row of samples, taken on the same frame for every series. A value is
+/// `None` when it could not be measured, e.g. speed with nothing selected.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    time: f64,
+    values: [Option<f32>; Series::ALL.len()],
+}
+
+#[derive(Resource)]
+pub struct PlotPanel {
+    pub visible: bool,
+    pub paused: bool,
+    samples: VecDeque<Sample>,
+}
+
+impl Default for PlotPanel {
+    fn default() -> Self {
+        PlotPanel {
+            visible: true,
+            paused: false,
+            samples: VecDeque::with_capacity(MAX_SAMPLES),
+        }
+    }
+}
+
+impl PlotPanel {
+    fn values(&self, series: usize) -> impl Iterator<Item = (usize, f32)> + '_ {
+        self.samples
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, sample)| sample.values[series].map(|v| (i, v)))
+    }
+
+    /// Writes every buffered sample to a CSV file in the working directory.
+    pub fn export_csv(&self) -> std::io::Result<String> {
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let path = format!("plot_{}.csv", stamp);
+        let mut file = BufWriter::new(File::create(&path)?);
+
+        let header = Series::ALL.iter().map(|s| s.csv_name()).collect::<Vec<_>>();
+        writeln!(file, "time,{}", header.join(","))?;
+        for sample in &self.samples {
+            let values = sample
+                .values
+                .iter()
+                .map(|v| v.map(|v| v.to_string()).unwrap_or_default())
+                .collect::<Vec<_>>();
+            writeln!(file, "{},{}", sample.time, values.join(","))?;
+        }
+        file.flush()?;
+        Ok(path)
+    }
+}
+
+#[derive(Component)]
+pub struct PlotLabel(usize);
+
+pub fn plot_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/start.ttf");
+
+    for (i, _) in Series::ALL.iter().enumerate() {
+        commands.spawn((
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font.clone(),
+                    font_size: 10.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(chart_top(i) - LABEL_HEIGHT),
+                right: Val::Px(MARGIN),
+                width: Val::Px(CHART_WIDTH),
+                ..default()
+            }),
+            PlotLabel(i),
+        ));
+    }
+}
+
+fn chart_top(index: usize) -> f32 {
+    MARGIN + LABEL_HEIGHT + index as f32 * (CHART_HEIGHT + LABEL_HEIGHT + CHART_GAP)
+}
+
+/// Records one sample of every series per frame from the `Body` query.
+pub fn plot_sample_system(
+    mut panel: ResMut<PlotPanel>,
+    bodies: Query<&Body>,
+    selection: Res<Selection>,
+    diagnostics: Res<ConservationDiagnostics>,
+    time: Res<Time>,
+) {
+    if panel.paused {
+        return;
+    }
+
+    let selected: Vec<&Body> = selection
+        .entities
+        .iter()
+        .filter_map(|e| bodies.get(*e).ok())
+        .collect();
+    let primary = selection.primary().and_then(|e| bodies.get(e).ok());
+    let center_of_mass = diagnostics.current.map(|c| c.center_of_mass.as_vec2());
+
+    let mut values = [None; Series::ALL.len()];
+    for (i, series) in Series::ALL.iter().enumerate() {
+        values[i] = match series {
+            Series::Fps => Some(1.0 / time.delta_seconds().max(1e-6)),
+            Series::EnergyError => diagnostics.energy_drift().map(|d| d as f32),
+            Series::Separation => match selected.as_slice() {
+                [.., a, b] => Some(Vec2::new(a.x - b.x, a.y - b.y).length()),
+                _ => None,
+            },
+            Series::Speed => primary.map(|b| Vec2::new(b.v_x, b.v_y).length()),
+            Series::OrbitalRadius => primary
+                .zip(center_of_mass)
+                .map(|(b, com)| Vec2::new(b.x, b.y).distance(com)),
+        };
+    }
+
+    if panel.samples.len() == MAX_SAMPLES {
+        panel.samples.pop_front();
+    }
+    panel.samples.push_back(Sample {
+        time: time.elapsed_seconds_f64(),
+        values,
+    });
+}
+
+/// Draws each series as a line chart pinned to the right edge of the screen.
+pub fn plot_draw_system(
+    panel: Res<PlotPanel>,
+    mut gizmos: Gizmos,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mut labels: Query<(&PlotLabel, &mut Text, &mut Visibility)>,
+) {
+    for (label, mut text, mut visibility) in labels.iter_mut() {
+        *visibility = if panel.visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        let series = Series::ALL[label.0];
+        let latest = panel.samples.back().and_then(|s| s.values[label.0]);
+        text.sections[0].value = match latest {
+            Some(value) => format!("{}: {:.3E}", series.label(), value),
+            None => format!("{}: -", series.label()),
+        };
+        if panel.paused {
+            text.sections[0].value.push_str(" (PAUSED)");
+        }
+    }
+
+    if !panel.visible {
+        return;
+    }
+    let window = windows.single();
+    let (camera, camera_transform) = camera_query.single();
+    let to_world = |screen: Vec2| camera.viewport_to_world_2d(camera_transform, screen);
+
+    let left = window.width() - MARGIN - CHART_WIDTH;
+    for (i, series) in Series::ALL.iter().enumerate() {
+        let top = chart_top(i);
+        let (Some(top_left), Some(bottom_right)) = (
+            to_world(Vec2::new(left, top)),
+            to_world(Vec2::new(left + CHART_WIDTH, top + CHART_HEIGHT)),
+        ) else {
+            continue;
+        };
+        let size = bottom_right - top_left;
+        gizmos.rect_2d(
+            (top_left + bottom_right) / 2.0,
+            0.0,
+            size.abs(),
+            Color::rgba(1.0, 1.0, 1.0, 0.4),
+        );
+
+        let (min, max) = panel
+            .values(i)
+            .fold((f32::MAX, f32::MIN), |(lo, hi), (_, v)| {
+                (lo.min(v), hi.max(v))
+            });
+        if min > max {
+            continue;
+        }
+        let range = (max - min).max(f32::EPSILON);
+        let points = panel.values(i).map(|(index, value)| {
+            let x = index as f32 / (MAX_SAMPLES - 1) as f32;
+            let y = (value - min) / range;
+            top_left + Vec2::new(x * size.x, (1.0 - y) * size.y)
+        });
+        gizmos.linestrip_2d(points, series.color());
+    }
+}
+
+pub fn plot_input_system(keyboard_input: Res<ButtonInput<KeyCode>>, mut panel: ResMut<PlotPanel>) {
+    if keyboard_input.just_pressed(KeyCode::F1) {
+        panel.visible = !panel.visible;
+    }
+    if keyboard_input.just_pressed(KeyCode::F2) {
+        panel.paused = !panel.paused;
+    }
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        match panel.export_csv() {
+            Ok(path) => info!("Exported charts to {}", path),
+            Err(err) => error!("Failed to export charts: {}", err),
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+
+use crate::GRAVITY_CONST;
+use crate::body::Body;
+use crate::colormap::Colormap;
+use crate::view_frame::{FrameTransform, ViewFrame};
+
+/// Only the heaviest bodies contribute to the sampled field, which keeps the
+/// per-pixel sums bounded in crowded scenes.
+const MAX_SOURCES: usize = 256;
+/// Field arrows are drawn every this many grid cells.
+const ARROW_SPACING: usize = 4;
+/// Streamlines start every this many grid cells.
+const STREAMLINE_SPACING: usize = 6;
+const STREAMLINE_STEPS: usize = 60;
+const HEATMAP_ALPHA: f32 = 0.7;
+
+/// How the acceleration field is drawn on top of the heatmap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldLines {
+    #[default]
+    Off,
+    Arrows,
+    Streamlines,
+}
+
+impl FieldLines {
+    pub fn next(self) -> Self {
+        match self {
+            FieldLines::Off => FieldLines::Arrows,
+            FieldLines::Arrows => FieldLines::Streamlines,
+            FieldLines::Streamlines => FieldLines::Off,
+        }
+    }
+}
+
+/// Background layers showing the gravitational potential and field of all
+/// bodies, sampled on a screen-space grid every frame.
+#[derive(Resource)]
+pub struct PotentialField {
+    pub heatmap: bool,
+    pub field_lines: FieldLines,
+    pub contours: bool,
+    pub colormap: Colormap,
+    /// Grid cell size in screen pixels.
+    pub cell_size: f32,
+    /// Number of contour levels across the visible range.
+    pub contour_levels: usize,
+}
+
+impl Default for PotentialField {
+    fn default() -> Self {
+        PotentialField {
+            heatmap: false,
+            field_lines: FieldLines::Off,
+            contours: false,
+            colormap: Colormap::Viridis,
+            cell_size: 8.0,
+            contour_levels: 12,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct PotentialHeatmap;
+
+/// A gravitating point with a softening radius so the field stays finite
+/// inside bodies.
+#[derive(Debug, Clone, Copy)]
+pub struct Source {
+    pub pos: Vec2,
+    pub mass: f32,
+    pub radius: f32,
+}
+
+impl Source {
+    pub fn potential(sources: &[Source], pos: Vec2) -> f32 {
+        sources
+            .iter()
+            .map(|s| -GRAVITY_CONST * s.mass / s.pos.distance(pos).max(s.radius))
+            .sum()
+    }
+
+    pub fn acceleration(sources: &[Source], pos: Vec2) -> Vec2 {
+        sources
+            .iter()
+            .map(|s| {
+                let direction = s.pos - pos;
+                let distance = direction.length().max(0.0001);
+                direction / distance * (GRAVITY_CONST * s.mass / distance.max(s.radius).powi(2))
+            })
+            .sum()
+    }
+}
+
+pub fn heaviest_sources(bodies: &Query<&Body>) -> Vec<Source> {
+    let mut sources = bodies
+        .iter()
+        .map(|body| Source {
+            pos: Vec2::new(body.x, body.y),
+            mass: body.mass,
+            radius: body.size.max(1.0),
+        })
+        .collect::<Vec<_>>();
+    if sources.len() > MAX_SOURCES {
+        sources.select_nth_unstable_by(MAX_SOURCES, |a, b| b.mass.total_cmp(&a.mass));
+        sources.truncate(MAX_SOURCES);
+    }
+    sources
+}
+
+/// Samples laid out over the screen, addressed in view frame coordinates.
+struct Grid {
+    width: usize,
+    height: usize,
+    /// View position of the top-left sample.
+    origin: Vec2,
+    /// View offset between neighbouring samples; `y` is negative since rows
+    /// run top to bottom.
+    step: Vec2,
+}
+
+impl Grid {
+    fn position(&self, i: usize, j: usize) -> Vec2 {
+        self.origin + Vec2::new(i as f32 * self.step.x, j as f32 * self.step.y)
+    }
+
+    fn contains(&self, pos: Vec2) -> bool {
+        let local = (pos - self.origin) / self.step;
+        local.x >= 0.0
+            && local.y >= 0.0
+            && local.x <= (self.width - 1) as f32
+            && local.y <= (self.height - 1) as f32
+    }
+}
+
+pub fn potential_field_setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: 1,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::linear();
+
+    commands.spawn((
+        SpriteBundle {
+            texture: images.add(image),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        PotentialHeatmap,
+    ));
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn potential_field_system(
+    mut gizmos: Gizmos,
+    settings: Res<PotentialField>,
+    frame: Res<ViewFrame>,
+    bodies: Query<&Body>,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mut images: ResMut<Assets<Image>>,
+    mut heatmap: Query<
+        (&Handle<Image>, &mut Sprite, &mut Transform, &mut Visibility),
+        With<PotentialHeatmap>,
+    >,
+) {
+    let (handle, mut sprite, mut transform, mut visibility) = heatmap.single_mut();
+    *visibility = Visibility::Hidden;
+    if !settings.heatmap && !settings.contours && settings.field_lines == FieldLines::Off {
+        return;
+    }
+
+    let sources = heaviest_sources(&bodies);
+    if sources.is_empty() {
+        return;
+    }
+    let window = windows.single();
+    let (camera, camera_transform) = camera_query.single();
+    let to_view = |screen: Vec2| camera.viewport_to_world_2d(camera_transform, screen);
+    let cell = settings.cell_size;
+    let (Some(origin), Some(next)) = (
+        to_view(Vec2::splat(cell * 0.5)),
+        to_view(Vec2::splat(cell * 1.5)),
+    ) else {
+        return;
+    };
+    let grid = Grid {
+        width: (window.width() / cell).ceil() as usize + 1,
+        height: (window.height() / cell).ceil() as usize + 1,
+        origin,
+        step: next - origin,
+    };
+
+    if settings.heatmap || settings.contours {
+        let values = normalized_potential(&grid, &sources, frame.transform);
+        if settings.heatmap
+            && let Some(image) = images.get_mut(handle)
+        {
+            write_heatmap(image, &grid, &values, settings.colormap);
+            let size = Vec2::new(grid.width as f32, grid.height as f32) * grid.step.abs();
+            let center = grid.position(grid.width - 1, grid.height - 1) * 0.5 + grid.origin * 0.5;
+            sprite.custom_size = Some(size);
+            // Behind the bodies, which sit at z = 0
+            *transform = Transform::from_translation(center.extend(-10.0));
+            *visibility = Visibility::Inherited;
+        }
+        if settings.contours {
+            draw_contours(&mut gizmos, &grid, &values, settings.contour_levels);
+        }
+    }
+
+    match settings.field_lines {
+        FieldLines::Off => {}
+        FieldLines::Arrows => draw_field_arrows(
+            &mut gizmos,
+            &grid,
+            &sources,
+            frame.transform,
+            settings.colormap,
+        ),
+        FieldLines::Streamlines => draw_streamlines(&mut gizmos, &grid, &sources, frame.transform),
+    }
+}
+
+/// Log of the well depth at each sample, scaled to `0..=1` over the screen
+/// so both shallow and deep wells stay visible.
+fn normalized_potential(grid: &Grid, sources: &[Source], frame: FrameTransform) -> Vec<f32> {
+    let mut values = Vec::with_capacity(grid.width * grid.height);
+    for j in 0..grid.height {
+        for i in 0..grid.width {
+            let world = frame.to_world(grid.position(i, j));
+            let depth = -Source::potential(sources, world);
+            values.push(depth.max(f32::MIN_POSITIVE).ln());
+        }
+    }
+
+    let (lo, hi) = values
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+    let range = (hi - lo).max(f32::EPSILON);
+    for value in values.iter_mut() {
+        *value = (*value - lo) / range;
+    }
+    values
+}
+
+fn write_heatmap(image: &mut Image, grid: &Grid, values: &[f32], colormap: Colormap) {
+    let size = Extent3d {
+        width: grid.width as u32,
+        height: grid.height as u32,
+        depth_or_array_layers: 1,
+    };
+    if image.texture_descriptor.size != size {
+        image.resize(size);
+    }
+    let alpha = (HEATMAP_ALPHA * 255.0) as u8;
+    for (pixel, value) in image.data.chunks_exact_mut(4).zip(values) {
+        let [r, g, b] = colormap.sample_rgb(*value);
+        pixel.copy_from_slice(&[
+            (r * 255.0) as u8,
+            (g * 255.0) as u8,
+            (b * 255.0) as u8,
+            alpha,
+        ]);
+    }
+}
+
+/// Marching squares over the normalized potential at evenly spaced levels.
+fn draw_contours(gizmos: &mut Gizmos, grid: &Grid, values: &[f32], levels: usize) {
+    let value = |i: usize, j: usize| values[j * grid.width + i];
+
+    for level in 0..levels {
+        let iso = (level as f32 + 0.5) / levels as f32;
+        let color = Color::rgba(1.0, 1.0, 1.0, 0.25 + 0.5 * iso);
+        for j in 0..grid.height - 1 {
+            for i in 0..grid.width - 1 {
+                // Corners in order around the cell
+                let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+                let mut crossings = Vec::with_capacity(4);
+                for edge in 0..4 {
+                    let (a, b) = (corners[edge], corners[(edge + 1) % 4]);
+                    let (va, vb) = (value(a.0, a.1), value(b.0, b.1));
+                    if (va < iso) != (vb < iso) {
+                        let t = (iso - va) / (vb - va);
+                        let pa = grid.position(a.0, a.1);
+                        crossings.push(pa + (grid.position(b.0, b.1) - pa) * t);
+                    }
+                }
+                for segment in crossings.chunks_exact(2) {
+                    gizmos.line_2d(segment[0], segment[1], color);
+                }
+            }
+        }
+    }
+}
+
+fn draw_field_arrows(
+    gizmos: &mut Gizmos,
+    grid: &Grid,
+    sources: &[Source],
+    frame: FrameTransform,
+    colormap: Colormap,
+) {
+    let mut arrows = Vec::new();
+    for j in (0..grid.height).step_by(ARROW_SPACING) {
+        for i in (0..grid.width).step_by(ARROW_SPACING) {
+            let pos = grid.position(i, j);
+            let acceleration = Source::acceleration(sources, frame.to_world(pos));
+            arrows.push((pos, frame.vector_to_view(acceleration)));
+        }
+    }
+
+    // Arrows have a fixed length; strength is shown by color on a log scale
+    let (lo, hi) = arrows
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), (_, a)| {
+            let magnitude = a.length().max(f32::MIN_POSITIVE).ln();
+            (lo.min(magnitude), hi.max(magnitude))
+        });
+    let range = (hi - lo).max(f32::EPSILON);
+    let length = 0.8 * ARROW_SPACING as f32 * grid.step.x.abs();
+    for (pos, acceleration) in arrows {
+        let magnitude = acceleration.length().max(f32::MIN_POSITIVE).ln();
+        let color = colormap.sample((magnitude - lo) / range);
+        gizmos.arrow_2d(pos, pos + acceleration.normalize_or_zero() * length, color);
+    }
+}
+
+/// Follows the field direction from evenly spaced seeds until a line falls
+/// into a body or leaves the screen.
+fn draw_streamlines(gizmos: &mut Gizmos, grid: &Grid, sources: &[Source], frame: FrameTransform) {
+    let step = grid.step.x.abs();
+    for j in (0..grid.height).step_by(STREAMLINE_SPACING) {
+        for i in (0..grid.width).step_by(STREAMLINE_SPACING) {
+            let mut pos = grid.position(i, j);
+            let mut line = vec![pos];
+            for _ in 0..STREAMLINE_STEPS {
+                let world = frame.to_world(pos);
+                if sources.iter().any(|s| s.pos.distance(world) < s.radius) {
+                    break;
+                }
+                let direction = frame
+                    .vector_to_view(Source::acceleration(sources, world))
+                    .normalize_or_zero();
+                if direction == Vec2::ZERO {
+                    break;
+                }
+                pos += direction * step;
+                if !grid.contains(pos) {
+                    break;
+                }
+                line.push(pos);
+            }
+            gizmos.linestrip_2d(line, Color::rgba(1.0, 1.0, 1.0, 0.35));
+        }
+    }
+}
+
+pub fn potential_field_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<PotentialField>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        settings.heatmap = !settings.heatmap;
+    }
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        settings.field_lines = settings.field_lines.next();
+    }
+    if keyboard_input.just_pressed(KeyCode::F7) {
+        settings.contours = !settings.contours;
+    }
+    if keyboard_input.just_pressed(KeyCode::F8) {
+        settings.colormap = settings.colormap.next();
+        info!("Colormap: {}", settings.colormap.label());
+    }
+}
@@ -0,0 +1,209 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use crate::body::Body;
+use crate::diagnostics::ConservationDiagnostics;
+use crate::history::HistoryRecorder;
+use crate::view_frame::ViewFrame;
+
+/// Bodies picked by the user, in the order they were selected. The most
+/// recently selected body is the primary one shown in panels.
+#[derive(Resource, Default)]
+pub struct Selection {
+    pub entities: Vec<Entity>,
+}
+
+impl Selection {
+    pub fn primary(&self) -> Option<Entity> {
+        self.entities.last().copied()
+    }
+
+    pub fn select_only(&mut self, entity: Entity) {
+        self.entities.clear();
+        self.entities.push(entity);
+    }
+
+    /// Adds the body to the selection, or removes it if already selected.
+    pub fn toggle(&mut self, entity: Entity) {
+        if let Some(index) = self.entities.iter().position(|e| *e == entity) {
+            self.entities.remove(index);
+        } else {
+            self.entities.push(entity);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+}
+
+/// Combined mass, position and velocity of a group of bodies.
+pub struct CenterOfMass {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub mass: f32,
+}
+
+impl CenterOfMass {
+    pub fn of<'a>(bodies: impl Iterator<Item = &'a Body>) -> Option<CenterOfMass> {
+        let (pos, vel, mass) = bodies.fold((Vec2::ZERO, Vec2::ZERO, 0.0), |(p, v, m), b| {
+            (
+                p + Vec2::new(b.x, b.y) * b.mass,
+                v + Vec2::new(b.v_x, b.v_y) * b.mass,
+                m + b.mass,
+            )
+        });
+        (mass > 0.0).then(|| CenterOfMass {
+            pos: pos / mass,
+            vel: vel / mass,
+            mass,
+        })
+    }
+}
+
+/// Returns the body under `pos`, preferring the one whose center is closest.
+/// Bodies smaller than `min_radius` are treated as that size so they stay
+/// clickable when zoomed out.
+pub fn pick<'a>(
+    bodies: impl Iterator<Item = (Entity, &'a Body)>,
+    pos: Vec2,
+    min_radius: f32,
+) -> Option<Entity> {
+    bodies
+        .map(|(entity, body)| {
+            let distance = Vec2::new(body.x, body.y).distance(pos);
+            (entity, distance, body.size.max(min_radius))
+        })
+        .filter(|(_, distance, radius)| distance <= radius)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(entity, _, _)| entity)
+}
+
+/// Drops selected entities that no longer exist.
+pub fn selection_cleanup_system(mut selection: ResMut<Selection>, bodies: Query<(), With<Body>>) {
+    if selection.entities.iter().any(|e| !bodies.contains(*e)) {
+        selection.entities.retain(|e| bodies.contains(*e));
+    }
+}
+
+/// Draws a ring around every selected body, brightest on the primary one.
+pub fn selection_highlight_system(
+    mut gizmos: Gizmos,
+    selection: Res<Selection>,
+    bodies: Query<&Body>,
+    frame: Res<ViewFrame>,
+    camera_query: Query<&Transform, With<Camera2d>>,
+) {
+    // Keep a few pixels between the body and the ring at any zoom
+    let padding = 4.0 * camera_query.single().scale.x;
+    let primary = selection.primary();
+    for entity in &selection.entities {
+        let Ok(body) = bodies.get(*entity) else {
+            continue;
+        };
+        let color = if Some(*entity) == primary {
+            Color::YELLOW
+        } else {
+            Color::rgba(1.0, 1.0, 0.6, 0.6)
+        };
+        let pos = frame.transform.to_view(Vec2::new(body.x, body.y));
+        gizmos.circle_2d(pos, body.size + padding, color);
+    }
+
+    // Mark the center of mass of a group
+    if selection.entities.len() > 1 {
+        let group = selection
+            .entities
+            .iter()
+            .filter_map(|e| bodies.get(*e).ok());
+        if let Some(center) = CenterOfMass::of(group) {
+            let pos = frame.transform.to_view(center.pos);
+            let arm = 3.0 * padding;
+            gizmos.line_2d(pos - Vec2::X * arm, pos + Vec2::X * arm, Color::YELLOW);
+            gizmos.line_2d(pos - Vec2::Y * arm, pos + Vec2::Y * arm, Color::YELLOW);
+        }
+    }
+}
+
+/// Shift-dragging on empty space draws a rubber band; every body inside it
+/// on release is added to the selection. Shift-clicks on bodies are handled
+/// by the editor.
+#[allow(clippy::too_many_arguments)]
+pub fn box_select_system(
+    mut gizmos: Gizmos,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    camera_transform_query: Query<&Transform, With<Camera2d>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut selection: ResMut<Selection>,
+    bodies: Query<(Entity, &Body)>,
+    frame: Res<ViewFrame>,
+    mut start: Local<Option<Vec2>>,
+) {
+    let window = windows.single();
+    let (camera, camera_transform) = camera_query.single();
+    let cursor = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor));
+
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if mouse_button_input.just_pressed(MouseButton::Left) && shift {
+        let pick_radius = 6.0 * camera_transform_query.single().scale.x;
+        *start = cursor.filter(|pos| {
+            pick(bodies.iter(), frame.transform.to_world(*pos), pick_radius).is_none()
+        });
+    }
+
+    let Some(start_pos) = *start else {
+        return;
+    };
+    let dragging = mouse_button_input.pressed(MouseButton::Left);
+    if !dragging {
+        *start = None;
+    }
+    let Some(cursor) = cursor else {
+        return;
+    };
+    let (min, max) = (start_pos.min(cursor), start_pos.max(cursor));
+    if dragging {
+        gizmos.rect_2d(
+            (min + max) / 2.0,
+            0.0,
+            max - min,
+            Color::rgba(1.0, 1.0, 0.6, 0.8),
+        );
+        return;
+    }
+
+    // A set keeps this linear when boxing thousands of bodies
+    let mut selected = selection.entities.iter().copied().collect::<HashSet<_>>();
+    for (entity, body) in bodies.iter() {
+        let pos = frame.transform.to_view(Vec2::new(body.x, body.y));
+        if pos.cmpge(min).all() && pos.cmple(max).all() && selected.insert(entity) {
+            selection.entities.push(entity);
+        }
+    }
+}
+
+/// `Delete` or `Backspace` removes the selected bodies.
+pub fn selection_delete_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut selection: ResMut<Selection>,
+    bodies: Query<(Entity, &Body)>,
+    mut recorder: HistoryRecorder,
+    mut diagnostics: ResMut<ConservationDiagnostics>,
+) {
+    if !keyboard_input.any_just_pressed([KeyCode::Delete, KeyCode::Backspace])
+        || selection.entities.is_empty()
+    {
+        return;
+    }
+    recorder.despawned(bodies.iter_many(&selection.entities));
+    for entity in selection.entities.drain(..) {
+        commands.entity(entity).despawn();
+    }
+    // The removed mass and energy are not drift
+    diagnostics.rebaseline();
+}
@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+use bevy::render::mesh::PrimitiveTopology;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::view::NoFrustumCulling;
+use bevy::sprite::MaterialMesh2dBundle;
+
+use crate::body::Body;
+use crate::potential_field::{Source, heaviest_sources};
+use crate::view_frame::ViewFrame;
+
+/// Rubber-sheet grid drawn behind the bodies. Seen from above, a vertex
+/// sinking into a well moves towards the mass pulling it, so each vertex is
+/// pulled along the local field by an amount proportional to the potential.
+#[derive(Resource)]
+pub struct SpacetimeGrid {
+    pub enabled: bool,
+    /// Approximate line spacing in screen pixels; the world spacing snaps to
+    /// powers of two as the camera zooms.
+    pub spacing: f32,
+    /// How far a vertex sinks per unit of potential depth, in world units.
+    pub depth_scale: f32,
+}
+
+impl Default for SpacetimeGrid {
+    fn default() -> Self {
+        SpacetimeGrid {
+            enabled: false,
+            spacing: 40.0,
+            depth_scale: 0.03,
+        }
+    }
+}
+
+/// Line-list mesh rebuilt every frame while the grid is shown. A mesh rather
+/// than gizmos so it can sit behind the bodies.
+#[derive(Component)]
+pub struct SpacetimeGridMesh;
+
+pub fn spacetime_grid_setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let mesh = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, Vec::<[f32; 3]>::new())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, Vec::<[f32; 4]>::new());
+
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: meshes.add(mesh).into(),
+            material: materials.add(ColorMaterial::from(Color::WHITE)),
+            // Between the potential heatmap and the bodies
+            transform: Transform::from_xyz(0.0, 0.0, -5.0),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        // The bounds change every frame
+        NoFrustumCulling,
+        SpacetimeGridMesh,
+    ));
+}
+
+pub fn spacetime_grid_system(
+    settings: Res<SpacetimeGrid>,
+    frame: Res<ViewFrame>,
+    bodies: Query<&Body>,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut grid_mesh: Query<(&Handle<Mesh>, &mut Visibility), With<SpacetimeGridMesh>>,
+) {
+    let (handle, mut visibility) = grid_mesh.single_mut();
+    *visibility = if settings.enabled {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    if !settings.enabled {
+        return;
+    }
+    let window = windows.single();
+    let (camera, camera_transform) = camera_query.single();
+    let to_view = |screen: Vec2| camera.viewport_to_world_2d(camera_transform, screen);
+    let (Some(top_left), Some(bottom_right)) = (
+        to_view(Vec2::ZERO),
+        to_view(Vec2::new(window.width(), window.height())),
+    ) else {
+        return;
+    };
+
+    // Snap spacing to a power of two so lines stay put while zooming smoothly
+    let units_per_pixel = (bottom_right.x - top_left.x) / window.width();
+    let spacing = 2f32.powf((settings.spacing * units_per_pixel).log2().round());
+    // One extra line beyond each edge so sunk vertices don't leave gaps
+    let min = (Vec2::new(top_left.x, bottom_right.y) / spacing).floor() - 1.0;
+    let max = (Vec2::new(bottom_right.x, top_left.y) / spacing).ceil() + 1.0;
+    let (columns, rows) = ((max.x - min.x) as usize + 1, (max.y - min.y) as usize + 1);
+
+    let sources = heaviest_sources(&bodies);
+    let max_sink = 4.0 * spacing;
+    let mut vertices = Vec::with_capacity(columns * rows);
+    for j in 0..rows {
+        for i in 0..columns {
+            let view = (min + Vec2::new(i as f32, j as f32)) * spacing;
+            let world = frame.transform.to_world(view);
+            let depth = -Source::potential(&sources, world);
+            let direction = frame
+                .transform
+                .vector_to_view(Source::acceleration(&sources, world))
+                .normalize_or_zero();
+            // Never sink past the nearest center, or lines fold over the well
+            let nearest = sources
+                .iter()
+                .map(|s| s.pos.distance(world))
+                .fold(f32::MAX, f32::min);
+            let sink = (depth * settings.depth_scale).min(max_sink).min(nearest);
+            let shade = 1.0 - 0.7 * (sink / max_sink);
+            let color = Color::rgba(0.3 * shade, 0.5 * shade, 1.0 * shade, 0.35);
+            vertices.push((view + direction * sink, color.as_linear_rgba_f32()));
+        }
+    }
+
+    let mut positions = Vec::with_capacity(4 * columns * rows);
+    let mut colors = Vec::with_capacity(4 * columns * rows);
+    let mut segment = |a: usize, b: usize| {
+        for (pos, color) in [vertices[a], vertices[b]] {
+            positions.push(pos.extend(0.0).to_array());
+            colors.push(color);
+        }
+    };
+    for j in 0..rows {
+        for i in 0..columns {
+            let index = j * columns + i;
+            if i + 1 < columns {
+                segment(index, index + 1);
+            }
+            if j + 1 < rows {
+                segment(index, index + columns);
+            }
+        }
+    }
+
+    if let Some(mesh) = meshes.get_mut(handle) {
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    }
+}
+
+pub fn spacetime_grid_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<SpacetimeGrid>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F9) {
+        settings.enabled = !settings.enabled;
+    }
+}
@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::body::{Body, BodyAssets, spawn_body};
+
+/// Marks a `Body` as a smoothed-particle hydrodynamics gas particle. Gas
+/// particles still gravitate through `Body`, and on top of that feel pressure
+/// and viscous forces from their neighbours.
+#[derive(Component, Debug, Clone, Copy, Reflect)]
+#[reflect(Component)]
+pub struct GasParticle {
+    pub smoothing_length: f32,
+    pub density: f32,
+    pub pressure: f32,
+}
+
+impl GasParticle {
+    pub fn new(smoothing_length: f32) -> Self {
+        GasParticle {
+            smoothing_length,
+            density: 0.0,
+            pressure: 0.0,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct SphSettings {
+    /// `K` in the polytropic equation of state `P = K * rho^gamma`.
+    pub pressure_constant: f32,
+    pub gamma: f32,
+    /// Linear (bulk) artificial viscosity coefficient.
+    pub viscosity_alpha: f32,
+    /// Quadratic (shock) artificial viscosity coefficient.
+    pub viscosity_beta: f32,
+    /// Number of particles in a spawned gas cloud.
+    pub cloud_particles: usize,
+}
+
+impl Default for SphSettings {
+    fn default() -> Self {
+        SphSettings {
+            pressure_constant: 0.05,
+            gamma: 5.0 / 3.0,
+            viscosity_alpha: 1.0,
+            viscosity_beta: 2.0,
+            cloud_particles: 400,
+        }
+    }
+}
+
+/// Estimates density and pressure for every gas particle, then adds the
+/// pressure gradient and artificial viscosity to its acceleration. Gas is
+/// kept outside solid bodies so it can settle into atmospheres.
+pub fn sph_system(
+    mut gas_query: Query<(&mut Body, &mut GasParticle)>,
+    solid_query: Query<&Body, Without<GasParticle>>,
+    settings: Res<SphSettings>,
+) {
+    let mut particles = gas_query.iter_mut().collect::<Vec<_>>();
+    if particles.is_empty() {
+        return;
+    }
+
+    let positions: Vec<Vec2> = particles
+        .iter()
+        .map(|(body, _)| Vec2::new(body.x, body.y))
+        .collect();
+    let velocities: Vec<Vec2> = particles
+        .iter()
+        .map(|(body, _)| Vec2::new(body.v_x, body.v_y))
+        .collect();
+    let masses: Vec<f32> = particles.iter().map(|(body, _)| body.mass).collect();
+    let lengths: Vec<f32> = particles
+        .iter()
+        .map(|(_, gas)| gas.smoothing_length)
+        .collect();
+
+    // Bucket particles on a grid as wide as the largest kernel support
+    let cell_size = 2.0 * lengths.iter().cloned().fold(0.0, f32::max);
+    let cell_of = |pos: Vec2| {
+        (
+            (pos.x / cell_size).floor() as i32,
+            (pos.y / cell_size).floor() as i32,
+        )
+    };
+    let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::default();
+    for (i, pos) in positions.iter().enumerate() {
+        grid.entry(cell_of(*pos)).or_default().push(i);
+    }
+    let neighbours = |i: usize| {
+        let (cx, cy) = cell_of(positions[i]);
+        (-1..=1)
+            .flat_map(move |dy| (-1..=1).map(move |dx| (cx + dx, cy + dy)))
+            .filter_map(|cell| grid.get(&cell))
+            .flatten()
+            .copied()
+    };
+
+    // Density and pressure
+    let mut densities = vec![0.0f32; particles.len()];
+    for i in 0..particles.len() {
+        for j in neighbours(i) {
+            let h = 0.5 * (lengths[i] + lengths[j]);
+            densities[i] += masses[j] * kernel(positions[i].distance(positions[j]), h);
+        }
+    }
+    let pressures: Vec<f32> = densities
+        .iter()
+        .map(|rho| settings.pressure_constant * rho.powf(settings.gamma))
+        .collect();
+    let sound_speeds: Vec<f32> = densities
+        .iter()
+        .zip(&pressures)
+        .map(|(rho, p)| (settings.gamma * p / rho).sqrt())
+        .collect();
+
+    // Pressure and viscous accelerations
+    for i in 0..particles.len() {
+        let mut acc = Vec2::ZERO;
+        for j in neighbours(i) {
+            if i == j {
+                continue;
+            }
+            let offset = positions[i] - positions[j];
+            let distance = offset.length();
+            let h = 0.5 * (lengths[i] + lengths[j]);
+            if distance >= 2.0 * h || distance == 0.0 {
+                continue;
+            }
+            let gradient = offset / distance * kernel_derivative(distance, h);
+
+            // Monaghan artificial viscosity, only for approaching pairs
+            let approach = (velocities[i] - velocities[j]).dot(offset);
+            let viscosity = if approach < 0.0 {
+                let mu = h * approach / (distance * distance + 0.01 * h * h);
+                let sound_speed = 0.5 * (sound_speeds[i] + sound_speeds[j]);
+                let density = 0.5 * (densities[i] + densities[j]);
+                (-settings.viscosity_alpha * sound_speed * mu + settings.viscosity_beta * mu * mu)
+                    / density
+            } else {
+                0.0
+            };
+
+            let pressure_term = pressures[i] / densities[i].powi(2)
+                + pressures[j] / densities[j].powi(2)
+                + viscosity;
+            acc -= gradient * masses[j] * pressure_term;
+        }
+
+        let (body, gas) = &mut particles[i];
+        body.a_x += acc.x;
+        body.a_y += acc.y;
+        gas.density = densities[i];
+        gas.pressure = pressures[i];
+    }
+
+    // Keep gas outside solid bodies, removing any inward velocity
+    for solid in solid_query.iter() {
+        let center = Vec2::new(solid.x, solid.y);
+        for (body, _) in particles.iter_mut() {
+            let offset = Vec2::new(body.x, body.y) - center;
+            let distance = offset.length();
+            if distance >= solid.size || distance == 0.0 {
+                continue;
+            }
+            let normal = offset / distance;
+            let surface = center + normal * solid.size;
+            body.x = surface.x;
+            body.y = surface.y;
+
+            let velocity = Vec2::new(body.v_x, body.v_y);
+            let inward = velocity.dot(normal).min(0.0);
+            body.v_x -= normal.x * inward;
+            body.v_y -= normal.y * inward;
+        }
+    }
+}
+
+/// 2D cubic spline smoothing kernel with support `2h`.
+fn kernel(r: f32, h: f32) -> f32 {
+    let q = r / h;
+    let sigma = 10.0 / (7.0 * std::f32::consts::PI * h * h);
+    if q < 1.0 {
+        sigma * (1.0 - 1.5 * q * q + 0.75 * q * q * q)
+    } else if q < 2.0 {
+        sigma * 0.25 * (2.0 - q).powi(3)
+    } else {
+        0.0
+    }
+}
+
+/// Radial derivative `dW/dr` of [`kernel`].
+fn kernel_derivative(r: f32, h: f32) -> f32 {
+    let q = r / h;
+    let sigma = 10.0 / (7.0 * std::f32::consts::PI * h * h);
+    if q < 1.0 {
+        sigma * (-3.0 * q + 2.25 * q * q) / h
+    } else if q < 2.0 {
+        -sigma * 0.75 * (2.0 - q).powi(2) / h
+    } else {
+        0.0
+    }
+}
+
+/// A disk of gas particles moving together, laid out on a sunflower spiral so
+/// the initial density is close to uniform.
+pub struct GasCloud {
+    pub center: Vec2,
+    pub velocity: Vec2,
+    pub radius: f32,
+    pub density: f32,
+    pub particles: usize,
+}
+
+impl GasCloud {
+    /// Spawns the particles and returns their entities.
+    pub fn spawn(&self, commands: &mut Commands, body_assets: &BodyAssets) -> Vec<Entity> {
+        let count = self.particles.max(1);
+        let spacing = self.radius * (std::f32::consts::PI / count as f32).sqrt();
+        let golden_angle = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+        let color = Color::rgba(0.6, 0.8, 1.0, 0.6);
+
+        (0..count)
+            .map(|i| {
+                let r = self.radius * ((i as f32 + 0.5) / count as f32).sqrt();
+                let angle = i as f32 * golden_angle;
+                let pos = self.center + Vec2::new(angle.cos(), angle.sin()) * r;
+
+                let mut body = Body::new(
+                    pos.x,
+                    pos.y,
+                    self.velocity.x,
+                    self.velocity.y,
+                    self.density,
+                    spacing * 0.5,
+                );
+                body.color = color;
+
+                spawn_body(commands, body_assets, body)
+                    .insert(GasParticle::new(spacing * 1.3))
+                    .id()
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::body::Body;
+use crate::body_color::BodyColoring;
+use crate::selection::Selection;
+use crate::sph::GasParticle;
+use crate::view_frame::{FrameKind, ViewFrame};
+
+const MIN_LENGTH: usize = 2;
+const MAX_LENGTH: usize = 2000;
+const LENGTH_STEP: usize = 30;
+const MIN_WIDTH: f32 = 0.5;
+const MAX_WIDTH: f32 = 8.0;
+const WIDTH_STEP: f32 = 0.5;
+
+/// Gizmo group for trails so their line width can be set independently of
+/// the other overlays.
+#[derive(Default, Reflect, GizmoConfigGroup)]
+pub struct TrailGizmos;
+
+/// Reference frame trail points are recorded in. Points are stored relative
+/// to the frame origin and drawn around its current position, so in a body
+/// frame the trails show motion as seen from that body. Either way they are
+/// drawn through the view frame like everything else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailFrame {
+    /// Whatever frame the scene is displayed in.
+    #[default]
+    View,
+    World,
+    CenterOfMass,
+    Body(Entity),
+}
+
+impl TrailFrame {
+    pub fn label(self) -> &'static str {
+        match self {
+            TrailFrame::View => "VIEW",
+            TrailFrame::World => "WORLD",
+            TrailFrame::CenterOfMass => "COM",
+            TrailFrame::Body(_) => "SELECTED",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrailColor {
+    /// The color the body leaving the trail is drawn with.
+    #[default]
+    Body,
+    /// Hue follows the direction of motion along the trail.
+    Heading,
+}
+
+impl TrailColor {
+    pub fn label(self) -> &'static str {
+        match self {
+            TrailColor::Body => "BODY",
+            TrailColor::Heading => "HEADING",
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct TrailSettings {
+    pub enabled: bool,
+    /// Number of past positions kept per body.
+    pub length: usize,
+    /// Line width in pixels.
+    pub width: f32,
+    /// Fade trails out towards their oldest point.
+    pub fade: bool,
+    pub frame: TrailFrame,
+    pub color: TrailColor,
+}
+
+impl Default for TrailSettings {
+    fn default() -> Self {
+        TrailSettings {
+            enabled: true,
+            length: 120,
+            width: 1.5,
+            fade: true,
+            frame: TrailFrame::View,
+            color: TrailColor::Body,
+        }
+    }
+}
+
+/// Past positions of a body relative to the trail frame, oldest first. In
+/// the view trail frame these are view coordinates.
+#[derive(Component, Default)]
+pub struct Trail {
+    points: VecDeque<Vec2>,
+}
+
+/// Gives every new solid body a trail. Gas particles are left out since a
+/// cloud of hundreds of trails only hides the flow.
+pub fn trail_attach_system(
+    mut commands: Commands,
+    bodies: Query<Entity, (Added<Body>, Without<GasParticle>)>,
+) {
+    for entity in bodies.iter() {
+        commands.entity(entity).insert(Trail::default());
+    }
+}
+
+/// Current position of the trail frame origin in world coordinates, or
+/// `None` if the frame body no longer exists. The view trail frame has no
+/// world origin of its own and gives zero.
+fn frame_origin(frame: TrailFrame, bodies: &Query<&Body>) -> Option<Vec2> {
+    match frame {
+        TrailFrame::View | TrailFrame::World => Some(Vec2::ZERO),
+        TrailFrame::CenterOfMass => {
+            let (weighted, total_mass) = bodies.iter().fold((Vec2::ZERO, 0.0), |(sum, m), b| {
+                (sum + Vec2::new(b.x, b.y) * b.mass, m + b.mass)
+            });
+            Some(if total_mass > 0.0 {
+                weighted / total_mass
+            } else {
+                Vec2::ZERO
+            })
+        }
+        TrailFrame::Body(entity) => bodies.get(entity).ok().map(|b| Vec2::new(b.x, b.y)),
+    }
+}
+
+/// Records the current position of every body in the trail frame. Trails
+/// are restarted whenever the frame their points were taken in changes,
+/// which for the view trail frame includes changes of the view frame.
+pub fn trail_record_system(
+    mut settings: ResMut<TrailSettings>,
+    frame: Res<ViewFrame>,
+    mut last_frame: Local<(TrailFrame, Option<FrameKind>)>,
+    mut trails: Query<(&Body, &mut Trail)>,
+    bodies: Query<&Body>,
+) {
+    let origin = frame_origin(settings.frame, &bodies);
+    if origin.is_none() {
+        // The frame body is gone; fall back to following the view
+        settings.frame = TrailFrame::View;
+    }
+    let current_frame = (
+        settings.frame,
+        (settings.frame == TrailFrame::View).then_some(frame.kind),
+    );
+    if current_frame != *last_frame {
+        *last_frame = current_frame;
+        for (_, mut trail) in trails.iter_mut() {
+            trail.points.clear();
+        }
+    }
+    let Some(origin) = origin.filter(|_| settings.enabled) else {
+        return;
+    };
+
+    for (body, mut trail) in trails.iter_mut() {
+        while trail.points.len() >= settings.length {
+            trail.points.pop_front();
+        }
+        let pos = Vec2::new(body.x, body.y);
+        trail.points.push_back(match settings.frame {
+            TrailFrame::View => frame.transform.to_view(pos),
+            _ => pos - origin,
+        });
+    }
+}
+
+pub fn trail_draw_system(
+    mut gizmos: Gizmos<TrailGizmos>,
+    mut config_store: ResMut<GizmoConfigStore>,
+    settings: Res<TrailSettings>,
+    trails: Query<(&Body, &Trail)>,
+    bodies: Query<&Body>,
+    frame: Res<ViewFrame>,
+    coloring: Res<BodyColoring>,
+) {
+    let (config, _) = config_store.config_mut::<TrailGizmos>();
+    config.line_width = settings.width;
+    if !settings.enabled {
+        return;
+    }
+    let Some(origin) = frame_origin(settings.frame, &bodies) else {
+        return;
+    };
+    let to_view = |point: Vec2| match settings.frame {
+        TrailFrame::View => point,
+        _ => frame.transform.to_view(point + origin),
+    };
+
+    for (body, trail) in trails.iter() {
+        let len = trail.points.len();
+        if len < 2 {
+            continue;
+        }
+        let points = trail.points.iter().enumerate().map(|(i, point)| {
+            let alpha = if settings.fade {
+                i as f32 / (len - 1) as f32
+            } else {
+                1.0
+            };
+            let color = match settings.color {
+                TrailColor::Body => coloring.color(body),
+                TrailColor::Heading => {
+                    let next = trail.points[(i + 1).min(len - 1)];
+                    let prev = trail.points[i.saturating_sub(1)];
+                    let direction = next - prev;
+                    let heading = direction
+                        .y
+                        .atan2(direction.x)
+                        .to_degrees()
+                        .rem_euclid(360.0);
+                    Color::hsl(heading, 0.8, 0.6)
+                }
+            };
+            (to_view(*point), color.with_a(color.a() * alpha))
+        });
+        gizmos.linestrip_gradient_2d(points);
+    }
+}
+
+pub fn trail_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<TrailSettings>,
+    mut trails: Query<&mut Trail>,
+    selection: Res<Selection>,
+) {
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let mut clear = false;
+
+    if keyboard_input.just_pressed(KeyCode::KeyT) {
+        if ctrl {
+            settings.color = match settings.color {
+                TrailColor::Body => TrailColor::Heading,
+                TrailColor::Heading => TrailColor::Body,
+            };
+        } else if shift {
+            settings.fade = !settings.fade;
+        } else {
+            settings.enabled = !settings.enabled;
+            clear = true;
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketLeft) {
+        settings.length = settings.length.saturating_sub(LENGTH_STEP).max(MIN_LENGTH);
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketRight) {
+        settings.length = (settings.length + LENGTH_STEP).min(MAX_LENGTH);
+    }
+    if keyboard_input.just_pressed(KeyCode::Comma) {
+        settings.width = (settings.width - WIDTH_STEP).max(MIN_WIDTH);
+    }
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        settings.width = (settings.width + WIDTH_STEP).min(MAX_WIDTH);
+    }
+    // Shift+N is the view frame
+    if keyboard_input.just_pressed(KeyCode::KeyN) && !shift {
+        // View -> world -> center of mass -> selected body (if any) -> view
+        settings.frame = match (settings.frame, selection.primary()) {
+            (TrailFrame::View, _) => TrailFrame::World,
+            (TrailFrame::World, _) => TrailFrame::CenterOfMass,
+            (TrailFrame::CenterOfMass, Some(selected)) => TrailFrame::Body(selected),
+            _ => TrailFrame::View,
+        };
+    }
+    if clear {
+        for mut trail in trails.iter_mut() {
+            trail.points.clear();
+        }
+    }
+}
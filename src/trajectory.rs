@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+
+use crate::body::Body;
+use crate::selection::Selection;
+use crate::view_frame::{FrameKind, FrameTransform, ViewFrame};
+use crate::{GRAVITY_CONST, SelectedBodyState};
+
+/// Only this many of the heaviest bodies pull on a predicted path, which
+/// keeps the preview cheap in crowded scenes.
+const MAX_SOURCES: usize = 64;
+
+/// Forward-integrated ghost path for the selected body and for the body
+/// being launched while dragging.
+#[derive(Resource)]
+pub struct TrajectoryPreview {
+    /// Draw the path of the selected body. The launch preview is always on.
+    pub show_selected: bool,
+    /// Number of steps to integrate ahead.
+    pub steps: usize,
+    /// Simulation time per step; the default matches one frame at 60 FPS.
+    pub step_size: f32,
+}
+
+impl Default for TrajectoryPreview {
+    fn default() -> Self {
+        TrajectoryPreview {
+            show_selected: true,
+            steps: 500,
+            step_size: 400.0 / 60.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PointMass {
+    entity: Option<Entity>,
+    pos: Vec2,
+    vel: Vec2,
+    mass: f32,
+}
+
+impl PointMass {
+    fn new(entity: Option<Entity>, body: &Body) -> Self {
+        PointMass {
+            entity,
+            pos: Vec2::new(body.x, body.y),
+            vel: Vec2::new(body.v_x, body.v_y),
+            mass: body.mass,
+        }
+    }
+}
+
+/// Integrates `test` together with `sources` using kick-drift-kick leapfrog
+/// and returns the positions `test` passes through, as seen in `frame` at
+/// each step. Falls back to `fallback` if the frame bodies aren't simulated.
+fn predict_path(
+    sources: &[PointMass],
+    test: PointMass,
+    frame: FrameKind,
+    fallback: FrameTransform,
+    steps: usize,
+    dt: f32,
+) -> Vec<Vec2> {
+    let mut bodies = sources.to_vec();
+    bodies.push(test);
+    let last = bodies.len() - 1;
+    let to_view = |bodies: &[PointMass]| {
+        let points = bodies
+            .iter()
+            .map(|b| (b.entity, b.pos, b.mass))
+            .collect::<Vec<_>>();
+        frame
+            .resolve(&points)
+            .unwrap_or(fallback)
+            .to_view(bodies[last].pos)
+    };
+
+    let mut path = Vec::with_capacity(steps + 1);
+    path.push(to_view(&bodies));
+    let mut acc = accelerations(&bodies);
+    for _ in 0..steps {
+        for (body, a) in bodies.iter_mut().zip(&acc) {
+            body.vel += *a * (0.5 * dt);
+            body.pos += body.vel * dt;
+        }
+        acc = accelerations(&bodies);
+        for (body, a) in bodies.iter_mut().zip(&acc) {
+            body.vel += *a * (0.5 * dt);
+        }
+        path.push(to_view(&bodies));
+    }
+    path
+}
+
+fn accelerations(bodies: &[PointMass]) -> Vec<Vec2> {
+    let mut acc = vec![Vec2::ZERO; bodies.len()];
+    for i in 0..bodies.len() {
+        for j in (i + 1)..bodies.len() {
+            let direction = bodies[j].pos - bodies[i].pos;
+            let distance = direction.length().max(0.0001);
+            let scale = GRAVITY_CONST / distance.powi(3);
+            acc[i] += direction * (scale * bodies[j].mass);
+            acc[j] -= direction * (scale * bodies[i].mass);
+        }
+    }
+    acc
+}
+
+/// The heaviest bodies other than `exclude`, used as field sources, plus any
+/// bodies the view frame is attached to so it can be followed.
+fn heaviest_sources(
+    bodies: &Query<(Entity, &Body)>,
+    exclude: Option<Entity>,
+    frame: FrameKind,
+) -> Vec<PointMass> {
+    let mut sources = bodies
+        .iter()
+        .filter(|(entity, _)| Some(*entity) != exclude)
+        .map(|(entity, body)| PointMass::new(Some(entity), body))
+        .collect::<Vec<_>>();
+    if sources.len() > MAX_SOURCES {
+        sources.select_nth_unstable_by(MAX_SOURCES, |a, b| b.mass.total_cmp(&a.mass));
+        sources.truncate(MAX_SOURCES);
+    }
+    for entity in frame.entities() {
+        if Some(entity) != exclude
+            && !sources.iter().any(|s| s.entity == Some(entity))
+            && let Ok((_, body)) = bodies.get(entity)
+        {
+            sources.push(PointMass::new(Some(entity), body));
+        }
+    }
+    sources
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn trajectory_preview_system(
+    mut gizmos: Gizmos,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    preview: Res<TrajectoryPreview>,
+    bodies: Query<(Entity, &Body)>,
+    selection: Res<Selection>,
+    selected_body_state: Res<SelectedBodyState>,
+    frame: Res<ViewFrame>,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<Camera2d>>,
+) {
+    if preview.show_selected
+        && let Some(selected) = selection.primary()
+        && let Ok((_, body)) = bodies.get(selected)
+    {
+        let sources = heaviest_sources(&bodies, Some(selected), frame.kind);
+        let path = predict_path(
+            &sources,
+            PointMass::new(Some(selected), body),
+            frame.kind,
+            frame.transform,
+            preview.steps,
+            preview.step_size,
+        );
+        gizmos.linestrip_2d(path, Color::rgba(1.0, 1.0, 1.0, 0.5));
+    }
+
+    // Launch preview while dragging out a new body
+    if !selected_body_state.pos_selected {
+        return;
+    }
+    let window = windows.single();
+    let (camera, camera_transform) = camera_query.single();
+    let Some(cursor) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world_2d(camera_transform, cursor))
+    else {
+        return;
+    };
+
+    // The drag is made in view coordinates
+    let fine = keyboard_input.any_pressed([KeyCode::AltLeft, KeyCode::AltRight]);
+    let (start, velocity) =
+        selected_body_state.launch(cursor, fine, frame.transform, bodies.iter());
+    let launch = Body::new(
+        start.x,
+        start.y,
+        velocity.x,
+        velocity.y,
+        selected_body_state.selected_density,
+        selected_body_state.selected_size,
+    );
+    let mut test = PointMass::new(None, &launch);
+    if selected_body_state.spawn_gas {
+        // A gas cloud is previewed as a massless tracer of its center
+        test.mass = 0.0;
+    }
+    let sources = heaviest_sources(&bodies, None, frame.kind);
+    let path = predict_path(
+        &sources,
+        test,
+        frame.kind,
+        frame.transform,
+        preview.steps,
+        preview.step_size,
+    );
+    gizmos.linestrip_2d(path, Color::rgba(0.4, 1.0, 0.4, 0.7));
+}
+
+pub fn trajectory_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut preview: ResMut<TrajectoryPreview>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyP) {
+        preview.show_selected = !preview.show_selected;
+    }
+}
@@ -0,0 +1,136 @@
+use bevy::prelude::*;
+
+use crate::GRAVITY_CONST;
+use crate::body::Body;
+use crate::selection::Selection;
+use crate::sph::GasParticle;
+use crate::view_frame::ViewFrame;
+
+/// At most this many pairwise contributions are drawn, strongest first.
+const MAX_PAIR_ARROWS: usize = 32;
+const SCALE_STEP: f32 = 1.25;
+
+/// Arrows drawn from each body for its velocity and net acceleration, and
+/// the pull of every other body on the selected one. Pairwise arrows share
+/// the acceleration scale so they add up to the net acceleration arrow.
+/// Vectors are world vectors rotated into the view frame.
+#[derive(Resource)]
+pub struct VectorOverlay {
+    pub velocity: bool,
+    pub acceleration: bool,
+    pub pairwise: bool,
+    /// Arrow length per unit of speed.
+    pub velocity_scale: f32,
+    /// Arrow length per unit of acceleration.
+    pub acceleration_scale: f32,
+}
+
+impl Default for VectorOverlay {
+    fn default() -> Self {
+        VectorOverlay {
+            velocity: false,
+            acceleration: false,
+            pairwise: false,
+            velocity_scale: 20.0,
+            acceleration_scale: 10.0,
+        }
+    }
+}
+
+pub fn vector_overlay_system(
+    mut gizmos: Gizmos,
+    overlay: Res<VectorOverlay>,
+    frame: Res<ViewFrame>,
+    selection: Res<Selection>,
+    bodies: Query<(Entity, &Body, Has<GasParticle>)>,
+) {
+    let transform = frame.transform;
+
+    if overlay.velocity || overlay.acceleration {
+        // Gas particles are skipped, a cloud of arrows is unreadable
+        for (_, body, _) in bodies.iter().filter(|(_, _, gas)| !gas) {
+            let start = transform.to_view(Vec2::new(body.x, body.y));
+            if overlay.velocity {
+                let velocity = transform.vector_to_view(Vec2::new(body.v_x, body.v_y));
+                gizmos.arrow_2d(
+                    start,
+                    start + velocity * overlay.velocity_scale,
+                    Color::LIME_GREEN,
+                );
+            }
+            if overlay.acceleration {
+                let acceleration =
+                    transform.vector_to_view(Vec2::new(body.last_a_x, body.last_a_y));
+                gizmos.arrow_2d(
+                    start,
+                    start + acceleration * overlay.acceleration_scale,
+                    Color::ORANGE,
+                );
+            }
+        }
+    }
+
+    if !overlay.pairwise {
+        return;
+    }
+    let Some((selected, target)) = selection
+        .primary()
+        .and_then(|e| bodies.get(e).ok())
+        .map(|(e, body, _)| (e, Vec2::new(body.x, body.y)))
+    else {
+        return;
+    };
+
+    // Same per-pair term as `pairwise_accelerations`, divided by our mass
+    let mut contributions = bodies
+        .iter()
+        .filter(|(entity, _, _)| *entity != selected)
+        .map(|(_, body, _)| {
+            let direction = Vec2::new(body.x, body.y) - target;
+            let distance = direction.length().max(0.0001);
+            direction / distance * (GRAVITY_CONST * body.mass / distance.powi(2))
+        })
+        .collect::<Vec<_>>();
+    if contributions.len() > MAX_PAIR_ARROWS {
+        contributions.select_nth_unstable_by(MAX_PAIR_ARROWS, |a, b| {
+            b.length_squared().total_cmp(&a.length_squared())
+        });
+        contributions.truncate(MAX_PAIR_ARROWS);
+    }
+
+    let start = transform.to_view(target);
+    for acceleration in contributions {
+        let end = start + transform.vector_to_view(acceleration) * overlay.acceleration_scale;
+        gizmos.arrow_2d(start, end, Color::rgba(1.0, 1.0, 0.3, 0.7));
+    }
+}
+
+pub fn vector_overlay_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut overlay: ResMut<VectorOverlay>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyU) {
+        overlay.velocity = !overlay.velocity;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyI) {
+        overlay.acceleration = !overlay.acceleration;
+    }
+    if keyboard_input.just_pressed(KeyCode::KeyJ) {
+        overlay.pairwise = !overlay.pairwise;
+    }
+
+    // Shift scales the acceleration arrows instead of the velocity arrows
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let factor = if keyboard_input.just_pressed(KeyCode::Equal) {
+        SCALE_STEP
+    } else if keyboard_input.just_pressed(KeyCode::Minus) {
+        1.0 / SCALE_STEP
+    } else {
+        return;
+    };
+    if shift {
+        overlay.acceleration_scale *= factor;
+    } else {
+        overlay.velocity_scale *= factor;
+    }
+}
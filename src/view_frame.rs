@@ -0,0 +1,165 @@
+use bevy::prelude::*;
+
+use crate::body::Body;
+use crate::selection::Selection;
+
+/// Reference frame the scene is displayed in. The simulation always runs in
+/// world coordinates; only rendering, trails and overlays are transformed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameKind {
+    #[default]
+    World,
+    /// Centered on the barycenter of all bodies.
+    Barycenter,
+    /// Centered on one body.
+    Body(Entity),
+    /// Centered on the barycenter of a pair and rotating with the line
+    /// between them, so the first body stays on the -x side and the second
+    /// on the +x side.
+    CoRotating(Entity, Entity),
+}
+
+impl FrameKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            FrameKind::World => "WORLD",
+            FrameKind::Barycenter => "BARYCENTER",
+            FrameKind::Body(_) => "SELECTED",
+            FrameKind::CoRotating(_, _) => "CO-ROTATING",
+        }
+    }
+
+    /// Bodies the frame is attached to.
+    pub fn entities(self) -> Vec<Entity> {
+        match self {
+            FrameKind::World | FrameKind::Barycenter => Vec::new(),
+            FrameKind::Body(entity) => vec![entity],
+            FrameKind::CoRotating(a, b) => vec![a, b],
+        }
+    }
+
+    /// Works out where the frame is from `(entity, position, mass)` triples.
+    /// Returns `None` if a body the frame is attached to is missing.
+    pub fn resolve(self, bodies: &[(Option<Entity>, Vec2, f32)]) -> Option<FrameTransform> {
+        let find = |target: Entity| {
+            bodies
+                .iter()
+                .find(|(entity, _, _)| *entity == Some(target))
+                .map(|&(_, pos, mass)| (pos, mass))
+        };
+
+        match self {
+            FrameKind::World => Some(FrameTransform::default()),
+            FrameKind::Barycenter => {
+                let (weighted, total_mass) = bodies
+                    .iter()
+                    .fold((Vec2::ZERO, 0.0), |(sum, m), &(_, pos, mass)| {
+                        (sum + pos * mass, m + mass)
+                    });
+                let origin = if total_mass > 0.0 {
+                    weighted / total_mass
+                } else {
+                    Vec2::ZERO
+                };
+                Some(FrameTransform {
+                    origin,
+                    rotation: 0.0,
+                })
+            }
+            FrameKind::Body(entity) => find(entity).map(|(origin, _)| FrameTransform {
+                origin,
+                rotation: 0.0,
+            }),
+            FrameKind::CoRotating(a, b) => {
+                let ((pos_a, mass_a), (pos_b, mass_b)) = (find(a)?, find(b)?);
+                let total_mass = mass_a + mass_b;
+                let origin = if total_mass > 0.0 {
+                    (pos_a * mass_a + pos_b * mass_b) / total_mass
+                } else {
+                    (pos_a + pos_b) / 2.0
+                };
+                let axis = pos_b - pos_a;
+                Some(FrameTransform {
+                    origin,
+                    rotation: axis.y.atan2(axis.x),
+                })
+            }
+        }
+    }
+}
+
+/// Placement of a frame in world coordinates at one instant.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameTransform {
+    pub origin: Vec2,
+    /// Angle of the frame's x axis from the world x axis, in radians.
+    pub rotation: f32,
+}
+
+impl FrameTransform {
+    pub fn to_view(self, world: Vec2) -> Vec2 {
+        self.vector_to_view(world - self.origin)
+    }
+
+    pub fn to_world(self, view: Vec2) -> Vec2 {
+        self.origin + self.vector_to_world(view)
+    }
+
+    /// Rotates a direction (velocity, force, ...) into the frame.
+    pub fn vector_to_view(self, world: Vec2) -> Vec2 {
+        Vec2::from_angle(-self.rotation).rotate(world)
+    }
+
+    pub fn vector_to_world(self, view: Vec2) -> Vec2 {
+        Vec2::from_angle(self.rotation).rotate(view)
+    }
+}
+
+/// The frame the scene is displayed in and where it currently is.
+#[derive(Resource, Default)]
+pub struct ViewFrame {
+    pub kind: FrameKind,
+    pub transform: FrameTransform,
+}
+
+/// Moves the view frame along with the bodies it follows, falling back to
+/// the world frame if one of them is gone.
+pub fn view_frame_system(mut frame: ResMut<ViewFrame>, bodies: Query<(Entity, &Body)>) {
+    let points = bodies
+        .iter()
+        .map(|(entity, body)| (Some(entity), Vec2::new(body.x, body.y), body.mass))
+        .collect::<Vec<_>>();
+    match frame.kind.resolve(&points) {
+        Some(transform) => frame.transform = transform,
+        None => {
+            frame.kind = FrameKind::World;
+            frame.transform = FrameTransform::default();
+        }
+    }
+}
+
+pub fn view_frame_input_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut frame: ResMut<ViewFrame>,
+    selection: Res<Selection>,
+) {
+    // Plain N is the trail frame
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if !(shift && keyboard_input.just_pressed(KeyCode::KeyN)) {
+        return;
+    }
+    // World -> barycenter -> selected body -> selected pair -> world,
+    // skipping the frames the current selection can't support
+    let pair = match selection.entities.as_slice() {
+        [.., a, b] => Some((*a, *b)),
+        _ => None,
+    };
+    frame.kind = match (frame.kind, selection.primary(), pair) {
+        (FrameKind::World, _, _) => FrameKind::Barycenter,
+        (FrameKind::Barycenter, Some(selected), _) => FrameKind::Body(selected),
+        (FrameKind::Barycenter | FrameKind::Body(_), _, Some((a, b))) => {
+            FrameKind::CoRotating(a, b)
+        }
+        _ => FrameKind::World,
+    };
+}